@@ -1,7 +1,8 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
-use std::sync::mpsc::{sync_channel, RecvError, SendError, TryRecvError, TrySendError};
-use std::{fs, thread};
-use crate::day9::computer_ver5;
+use std::fs;
+use crate::intcode::io::{run_io, InputFn, OutputFn};
+use crate::intcode::vm::Vm;
 
 const UP: char = '^';
 const DOWN: char = 'v';
@@ -9,7 +10,7 @@ const LEFT: char = '<';
 const RIGHT: char = '>';
 
 #[derive(Debug)]
-struct Canvas {
+pub struct Canvas {
     prev_direction: char,
     location: (usize, usize),
     pixels: Vec<Vec<char>>,
@@ -108,61 +109,53 @@ impl Canvas {
         fs::write("./outputs/day11", res_str).unwrap();
     }
 }
-pub fn run_computer(vec: Vec<i64>) {
-    let (sender1, receiver2) = sync_channel(10);
-    let (sender2, receiver1) = sync_channel(10);
-
-    let handle = thread::spawn(move || { computer_ver5(vec, sender1, receiver1) });
-
-    let mut canvas = Canvas::new();
-    let painter = thread::spawn(move || {
-        loop {
-            let (x, y) = canvas.location;
-            // println!("{}", canvas.pixels[x][y]);
-            let cur_col = match canvas.pixels[x][y] {
-                '.' => { 0 }
-                '#' => { 1 }
-                _ => { break; }
-            };
-            match sender2.try_send(cur_col) {
-                Ok(_) => {}
-                Err(TrySendError::Disconnected(..)) => { break; }
-                Err(e) => { eprintln!("{e}") }
-            };
-            let color = match receiver2.recv() {
-                Ok(color) => { color as usize }
-                // Err(TryRecvError::Disconnected) => {eprintln!("disconnected"); break;},
-                Err(e) => {
-                    eprintln!("{e:?}");
-                    break;
-                }
-            };
-
-            let rotation = match receiver2.recv() {
-                Ok(rotation) => { rotation as usize }
-                // Err(TryRecvError::Disconnected) => {eprintln!("disconnected");break;},
-                Err(e) => {
-                    eprintln!("{e:?}");
-                    break;
-                }
-            };
-
 
+// the robot reads the color under it and answers with a color to paint and a turn
+pub fn paint(vec: Vec<i64>) -> Result<Canvas, String> {
+    let canvas = RefCell::new(Canvas::new());
+    let mut color = None;
+    let mut camera = InputFn(|| {
+        let canvas = canvas.borrow();
+        let (x, y) = canvas.location;
+        match canvas.pixels[x][y] {
+            '.' => Some(0),
+            '#' => Some(1),
+            _ => None,
+        }
+    });
+    let mut controls = OutputFn(|value| match color.take() {
+        None => color = Some(value as usize),
+        Some(color) => {
+            let mut canvas = canvas.borrow_mut();
             canvas.paint_canvas(color);
-            canvas.move_arrow(rotation);
+            canvas.move_arrow(value as usize);
         }
-        canvas
     });
+    run_io(&mut Vm::new(vec), &mut camera, &mut controls)?;
+    Ok(canvas.into_inner())
+}
+
+pub fn run_computer(vec: Vec<i64>) {
+    match paint(vec) {
+        Ok(canvas) => {
+            canvas.save_canvas();
+            println!("{:?}", canvas.painted_areas.len());
+        }
+        Err(e) => eprintln!("{e}"),
+    }
+}
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::load_program;
 
-    let res = handle.join();
-    let output = painter.join().unwrap();
-    output.save_canvas();
-    println!("{:?}", output.painted_areas.len());
+    #[test]
+    fn test_robot_eye_message() {
+        let canvas = paint(load_program("./inputs/day11").unwrap()).unwrap();
+        assert_eq!(canvas.painted_areas.len(), 92);
+        let rows: Vec<String> = canvas.pixels.iter().map(|row| row.iter().collect()).collect();
+        assert_eq!(rows.iter().filter(|row| row.contains('#')).count(), 32);
+        assert_eq!(rows.iter().find(|row| row.contains('#')).unwrap().trim_matches('.'), "######");
+    }
 }
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//     #[test]
-//     fn test_robot_eye_message() {}
-// }
\ No newline at end of file
@@ -12,6 +12,7 @@ use ruscii::drawing::{Pencil};
 use ruscii::keyboard::{KeyEvent, Key};
 use ruscii::spatial::{Vec2};
 use ruscii::gui::{FPSCounter};
+use crate::intcode::patch::free_play;
 
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -91,8 +92,8 @@ fn parse_instruction(instruction: i64) -> (Opcode, [ParamMode; 3]) {
         ParamMode::try_from(instruction_str[0..1].to_string().parse::<u8>().unwrap()).unwrap();
     (opcode, [param1, param2, param3])
 }
-struct Computer {
-    memory: Vec<i64>,
+pub(crate) struct Computer {
+    pub(crate) memory: Vec<i64>,
     memory_start_index: usize,
     instruction_pointer: usize,
     relative_base: i64,
@@ -102,7 +103,7 @@ struct Computer {
 }
 
 impl Computer {
-    fn new(program: Vec<i64>, memory_size: usize, sender: SyncSender<i64>, receiver: Receiver<i64>) -> Self {
+    pub(crate) fn new(program: Vec<i64>, memory_size: usize, sender: SyncSender<i64>, receiver: Receiver<i64>) -> Self {
         let mut memory = program.clone();
         let mut empty_mem = Vec::new();
         empty_mem.resize(memory_size, 0);
@@ -111,11 +112,7 @@ impl Computer {
         Self { memory, memory_start_index: program.len(), instruction_pointer: 0, relative_base: 0, sender, receiver, result: -1 }
     }
 
-    fn insert_at_mem(&mut self, index: usize, val: i64) {
-        self.memory[index] = val;
-    }
-
-    fn run(&mut self) {
+    pub(crate) fn run(&mut self) {
         // let mut vec = self.memory.clone();
         let instruction_pointer = self.instruction_pointer;
         loop {
@@ -302,10 +299,9 @@ pub fn run_computer_with_ruscii(mut vec: Vec<i64>) {
     let (sender1, receiver2) = sync_channel(100);
     let (sender2, receiver1) = sync_channel(100);
 
+    free_play().apply(&mut vec).expect("not a breakout program");
     let mut computer = Computer::new(vec, 16000, sender1, receiver1);
 
-    computer.insert_at_mem(0, 2);
-
 
     let handle = thread::spawn(move || { computer.run() });
 
@@ -1,3 +1,7 @@
+use crate::intcode::patch::PatchSet;
+use crate::intcode::search::Search;
+use crate::intcode::symbolic::evaluate;
+
 
 pub fn computer(mut vec: Vec<u32>) -> Vec<u32> {
     let mut index = 0;
@@ -29,16 +33,55 @@ pub fn computer(mut vec: Vec<u32>) -> Vec<u32> {
     vec
 }
 
+// runs `vec` with a patch set applied, e.g. `patch::alarm_1202()` for part one
+pub fn patched_computer(vec: Vec<u32>, patches: &PatchSet) -> Result<Vec<u32>, String> {
+    let mut program: Vec<i64> = vec.into_iter().map(i64::from).collect();
+    patches.apply(&mut program)?;
+    let program = program.into_iter()
+        .map(|value| u32::try_from(value).map_err(|_| format!("patch set {}: {} doesn't fit in a day2 cell", patches.name, value)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(computer(program))
+}
+
 pub fn pair_computer(vec: Vec<u32>, result: u32) -> Result<(u32,u32), String>{
-    for i in 0..=99{
-        for j in 0..=99{
-            let mut vec_clone = vec.clone();
-            vec_clone[1] = i;
-            vec_clone[2] = j;
-            let res = computer(vec_clone)[0];
-            if res == result {return Ok((i,j))};
-        }
+    let program: Vec<i64> = vec.into_iter().map(i64::from).collect();
+    let ranges = [(1, 0..=99), (2, 0..=99)];
+    // memory[0] is usually linear in the noun and verb, so solve for it and only fall back
+    // to trying every pair when it isn't
+    let matches = match evaluate(&program, &[1, 2], &[]).and_then(|evaluation| evaluation.read(0)?.solve(i64::from(result), &ranges)) {
+        Ok(matches) => matches,
+        Err(_) => Search::new(program)
+            .patch(1, 0..=99)
+            .patch(2, 0..=99)
+            .find(|vm| vm.read(0) == i64::from(result))?,
+    };
+    match matches.first() {
+        Some(pair) => Ok((pair[0] as u32, pair[1] as u32)),
+        None => Err("No pairs satisfy the result".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program;
+    use crate::intcode::patch::alarm_1202;
+    use crate::intcode::vm::Vm;
+
+    fn create_vec() -> Vec<u32> {
+        load_program("./inputs/day2").unwrap().into_iter().map(|value| value as u32).collect()
+    }
+
+    #[test]
+    fn test_patched_computer() {
+        let result = patched_computer(create_vec(), &alarm_1202()).unwrap();
+        let mut vm = alarm_1202().vm(load_program("./inputs/day2").unwrap()).unwrap();
+        vm.run().unwrap();
+        assert_eq!(result[0] as i64, vm.read(0));
+
+        let err = patched_computer(vec![1, 0, 0, 0, 99], &PatchSet::new("far").set(5, 1)).unwrap_err();
+        assert_eq!(err, "patch set far: address 5 is outside the program (5 cells)");
+        let err = patched_computer(vec![1, 0, 0, 0, 99], &PatchSet::new("negative").set(1, -1)).unwrap_err();
+        assert_eq!(err, "patch set negative: -1 doesn't fit in a day2 cell");
     }
-    Err("No pairs satisfy the result".to_string())
-    
 }
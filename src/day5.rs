@@ -1,4 +1,4 @@
-use std::{io, num::ParseIntError};
+use crate::intcode::io::{Input, StdinInput};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ParamMode {
@@ -74,17 +74,21 @@ pub(crate) fn get_mut_param(vec: &mut Vec<i32>, index: usize, param_mode: ParamM
     }
 }
 
-pub(crate) fn user_input() -> Result<i32, ParseIntError> {
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("error: unable to read user input");
-    input.trim().parse::<i32>()
+pub fn computer_ver2(vec: Vec<i32>) -> i32 {
+    let mut terminal = StdinInput { prompt: Some("Please enter an input instruction: ".to_string()) };
+    let mut result = 0;
+    computer_ver2_with_io(vec, || {
+        terminal.read().unwrap().expect("error: no input left") as i32
+    }, |value| {
+        result = value;
+        println!("OUTPUT: {}", result);
+    });
+    result
 }
 
-pub fn computer_ver2(mut vec: Vec<i32>) -> i32 {
+// same machine with the input and output hooked up by the caller, returns the final memory
+pub(crate) fn computer_ver2_with_io(mut vec: Vec<i32>, mut input: impl FnMut() -> i32, mut output: impl FnMut(i32)) -> Vec<i32> {
     let mut instruction_pointer = 0;
-    let mut result = 0;
     loop {
         let (opcode, param_modes) = parse_instruction(vec[instruction_pointer]);
 
@@ -106,14 +110,12 @@ pub fn computer_ver2(mut vec: Vec<i32>) -> i32 {
             Opcode::Input => {
                 let param1 = get_mut_param(&mut vec, instruction_pointer + 1, param_modes[0]);
 
-                println!("Please enter an input instruction: ");
-                *param1 = user_input().unwrap();
+                *param1 = input();
                 instruction_pointer += 2;
             }
             Opcode::Output => {
                 let param1 = get_param(&vec, instruction_pointer + 1, param_modes[0]);
-                result = param1;
-                println!("OUTPUT: {}", result);
+                output(param1);
                 instruction_pointer += 2;
             }
             Opcode::JumpIfTrue => {
@@ -163,7 +165,7 @@ pub fn computer_ver2(mut vec: Vec<i32>) -> i32 {
             }
         }
     }
-    result
+    vec
 }
 
 
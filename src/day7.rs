@@ -2,10 +2,21 @@ use std::collections::BTreeSet;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
 use std::thread;
 use crate::day5::*;
+use crate::intcode::amplifier::{AmplifierChain, Permutations};
 
-pub fn computer_ver3(mut vec: Vec<i32>, inputs: Vec<i32>, output: &mut i32) -> i32 {
+pub fn computer_ver3(vec: Vec<i32>, inputs: Vec<i32>, output: &mut i32) -> i32 {
+    let mut outputs = vec![];
+    computer_ver3_with_memory(vec, inputs, &mut outputs);
+    if let Some(last) = outputs.last() {
+        *output = *last;
+    }
+    // the program has stopped
+    0
+}
+
+// collects every output instead of just the last one, returns the final memory
+pub(crate) fn computer_ver3_with_memory(mut vec: Vec<i32>, inputs: Vec<i32>, outputs: &mut Vec<i32>) -> Vec<i32> {
     let mut instruction_pointer = 0;
-    let mut result;
     let mut input_counter = 0;
     loop {
         let (opcode, param_modes) = parse_instruction(vec[instruction_pointer]);
@@ -34,8 +45,8 @@ pub fn computer_ver3(mut vec: Vec<i32>, inputs: Vec<i32>, output: &mut i32) -> i
             }
             Opcode::Output => {
                 let param1 = get_param(&vec, instruction_pointer + 1, param_modes[0]);
-                *output = param1;
-                // println!("OUTPUT: {}", result);
+                outputs.push(param1);
+                // println!("OUTPUT: {}", param1);
                 instruction_pointer += 2;
             }
             Opcode::JumpIfTrue => {
@@ -81,66 +92,33 @@ pub fn computer_ver3(mut vec: Vec<i32>, inputs: Vec<i32>, output: &mut i32) -> i
                 instruction_pointer += 4;
             }
             Opcode::Stop => {
-                result = 0;
                 break;
             }
         }
     }
-    result
+    vec
 }
 
-fn phase_setter(mut vec: Vec<i32>, phase_setting: Vec<i32>) -> i32 {
-    //also the first input of 0
-    let mut last_result = 0;
-    let mut output = 0;
-
-    for i in 0..5 {
-        // println!("{last_result}");
-        computer_ver3(vec.clone(), vec![phase_setting[i], output], &mut output);
-    }
-    output
+fn to_program(vec: &[i32]) -> Vec<i64> {
+    vec.iter().map(|value| *value as i64).collect()
 }
 
-fn rec_get_permutations(generated_perms: &mut Vec<Vec<i32>>, current_perm: &mut Vec<i32>, mut elements_to_permute: BTreeSet<i32>) {
-    if !elements_to_permute.is_empty() {
-        for element in elements_to_permute.clone() {
-            let next_perm = &mut current_perm.clone();
-            next_perm.push(element.clone());
-            let mut remaining_elements = elements_to_permute.clone();
-            remaining_elements.remove(&element);
-            rec_get_permutations(generated_perms, next_perm, remaining_elements);
-        }
-    } else {
-        generated_perms.push(current_perm.clone());
-    }
+// the highest signal over every order of `ints`, any number of amplifiers
+pub fn try_phase_combinations(mut vec: Vec<i32>, ints: Vec<i32>) -> Result<i32, String> {
+    let best = AmplifierChain::new(to_program(&vec)).best(&to_program(&ints))?;
+    best.map(|(signal, _)| signal as i32).ok_or_else(|| "no phase settings".to_string())
 }
-pub fn try_phase_combinations(mut vec: Vec<i32>, ints: Vec<i32>) -> i32 {
-    let mut max = 0;
-    let mut set = BTreeSet::from_iter(ints);
-    let mut perms = vec![];
-    let mut cur_perm = vec![];
-    rec_get_permutations(&mut perms, &mut cur_perm, set);
-
-
-    for p in perms {
-        let res = phase_setter(vec.clone(), p);
-        // println!("{res}");
-        if res > max { max = res };
-    }
-    max
+// Every amplifier keeps running from where it stopped, so each one reads its phase only
+// once; the signal goes around until they have all halted.
+fn phase_setter_feedback_loop(mut vec: Vec<i32>, phase_setting: Vec<i32>) -> Result<i32, String> {
+    let signal = AmplifierChain::new(to_program(&vec)).feedback().signal(&to_program(&phase_setting))?;
+    Ok(signal as i32)
 }
-fn phase_setter_feedback_loop(mut vec: Vec<i32>, phase_setting: Vec<i32>) -> i32 {
-    //also the first input of 0
-    let mut last_result = 0;
-    let mut output = 0;
 
-    loop {
-        for i in 0..5 {
-            last_result = computer_ver3(vec.clone(), vec![phase_setting[i], output.clone()], &mut output);
-        }
-        if last_result == 0 { break; }
-    }
-    output
+// same as `threaded_feedback_loop_phase_combinations`, without a thread per amplifier
+pub fn feedback_loop_phase_combinations(mut vec: Vec<i32>, ints: Vec<i32>) -> Result<i32, String> {
+    let best = AmplifierChain::new(to_program(&vec)).feedback().best(&to_program(&ints))?;
+    best.map(|(signal, _)| signal as i32).ok_or_else(|| "no phase settings".to_string())
 }
 
 //Threaded
@@ -174,9 +152,10 @@ pub fn computer_ver4(mut vec: Vec<i32>, sender: SyncSender<i32>, receiver: Recei
             }
             Opcode::Output => {
                 let param1 = get_param(&vec, instruction_pointer + 1, param_modes[0]);
+                // the last output is the result, whether or not the next amplifier is still listening
+                result = param1;
                 match sender.try_send(param1) {
-                    Ok(_) => {},
-                    Err(TrySendError::Disconnected(i32)) => {result = param1}
+                    Ok(_) | Err(TrySendError::Disconnected(_)) => {},
                     Err(e) => {eprintln!("Received {e} error!")}
                 };
                 // println!("OUTPUT: {}", result);
@@ -233,64 +212,44 @@ pub fn computer_ver4(mut vec: Vec<i32>, sender: SyncSender<i32>, receiver: Recei
     result
 }
 
+// One thread per amplifier, each reading from its own channel and writing into the next
+// one's, the last back into the first.
 fn threaded_phase_setter(mut vec: Vec<i32>, phase_setting: Vec<i32>) -> i32 {
-    //also the first input of 0
-    let mut last_result = 0;
-    // let mut output = 0;
-
-    let (amp1_send, amp2_recv) = sync_channel(5);
-    let (amp2_send, amp3_recv) = sync_channel(5);
-    let (amp3_send, amp4_recv) = sync_channel(5);
-    let (amp4_send, amp5_recv) = sync_channel(5);
-    let (amp5_send, amp1_recv) = sync_channel(5);
+    if phase_setting.is_empty() {
+        return 0;
+    }
+    let (senders, receivers): (Vec<SyncSender<i32>>, Vec<Receiver<i32>>) = phase_setting.iter().map(|_| sync_channel(5)).unzip();
 
-    let sender = amp1_send.clone();
-    let cloned_vec = vec.clone();
-    let amp1 = thread::spawn(move || { computer_ver4(cloned_vec, sender, amp1_recv) });
-    let sender = amp2_send.clone();
-    let cloned_vec = vec.clone();
-    let amp2 = thread::spawn(move || { computer_ver4(cloned_vec, sender, amp2_recv) });
-    let sender = amp3_send.clone();
-    let cloned_vec = vec.clone();
-    let amp3 = thread::spawn(move || { computer_ver4(cloned_vec, sender, amp3_recv) });
-    let sender = amp4_send.clone();
-    let cloned_vec = vec.clone();
-    let amp4 = thread::spawn(move || { computer_ver4(cloned_vec, sender, amp4_recv) });
-    let sender = amp5_send.clone();
-    let cloned_vec = vec.clone();
-    let amp5 = thread::spawn(move || { computer_ver4(cloned_vec, sender, amp5_recv) });
+    // every phase has to be queued before the first signal, or an amplifier can read the
+    // previous one's output as its phase
+    for (sender, phase) in senders.iter().zip(&phase_setting) {
+        sender.send(*phase).expect("Couldn't send phase setting!");
+    }
+    senders[0].send(0).expect("Couldn't send init val!");
 
-    amp5_send.send(phase_setting[0]).expect("Couldn't send phase setting!");
-    amp5_send.send(0).expect("Couldn't send init val!");
-    amp1_send.send(phase_setting[1]).expect("Couldn't send phase setting!");
-    amp2_send.send(phase_setting[2]).expect("Couldn't send phase setting!");
-    amp3_send.send(phase_setting[3]).expect("Couldn't send phase setting!");
-    amp4_send.send(phase_setting[4]).expect("Couldn't send phase setting!");
+    let amplifiers: Vec<_> = receivers.into_iter().enumerate().map(|(i, receiver)| {
+        let sender = senders[(i + 1) % senders.len()].clone();
+        let cloned_vec = vec.clone();
+        thread::spawn(move || { computer_ver4(cloned_vec, sender, receiver) })
+    }).collect();
 
-    amp1.join().unwrap();
-    amp2.join().unwrap();
-    amp3.join().unwrap();
-    amp4.join().unwrap();
-    amp5.join().unwrap()
+    let mut last_result = 0;
+    for amplifier in amplifiers {
+        last_result = amplifier.join().unwrap();
+    }
+    last_result
 }
 
 pub fn threaded_feedback_loop_phase_combinations(mut vec: Vec<i32>, ints: Vec<i32>) -> i32 {
-    let mut max = 0;
-    let mut set = BTreeSet::from_iter(ints);
-    let mut perms = vec![];
-    let mut cur_perm = vec![];
-    rec_get_permutations(&mut perms, &mut cur_perm, set);
-
-    for p in perms {
-        let res = threaded_phase_setter(vec.clone(), p);
-        // println!("{res}");
-        if res > max { max = res };
-    }
-    max
+    Permutations::new(ints)
+        .map(|p| threaded_phase_setter(vec.clone(), p))
+        .max()
+        .unwrap_or(0)
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode::load_program;
     fn create_vec() -> Vec<i32> {
         vec![3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0]
     }
@@ -299,27 +258,25 @@ mod tests {
     fn test_amplifier_io() {
         let mut vec = create_vec();
         let phase_setting = vec![4, 3, 2, 1, 0];
-        assert_eq!(43210, phase_setter(vec, phase_setting));
+        assert_eq!(43210, AmplifierChain::new(to_program(&vec)).signal(&to_program(&phase_setting)).unwrap());
         let mut vec = vec![3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33,
                            1002, 33, 7, 33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0];
         let phase_setting = vec![1, 0, 4, 3, 2];
-        assert_eq!(65210, phase_setter(vec, phase_setting));
+        assert_eq!(65210, AmplifierChain::new(to_program(&vec)).signal(&to_program(&phase_setting)).unwrap());
     }
     #[test]
     fn test_phase_perms() {
-        let vec = vec![0, 1, 2, 3, 4];
-        let mut result_vec = vec![];
-        let mut cur_perm = vec![];
-        rec_get_permutations(&mut result_vec, &mut cur_perm, BTreeSet::from_iter(vec));
-
-        println!("{result_vec:?}");
+        let result_vec: Vec<Vec<i32>> = Permutations::new(vec![0, 1, 2, 3, 4]).collect();
+        assert_eq!(result_vec.len(), 120);
+        assert_eq!(BTreeSet::from_iter(result_vec).len(), 120);
     }
     #[test]
     fn test_max_from_phase_combinations() {
         let mut vec = vec![3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33,
                            1002, 33, 7, 33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0];
         let set = vec![0, 1, 2, 3, 4];
-        println!("{}", try_phase_combinations(vec, set));
+        assert_eq!(65210, try_phase_combinations(vec, set).unwrap());
+        assert!(try_phase_combinations(vec![99], vec![0, 1]).unwrap_err().contains("produced no output"));
     }
     #[test]
     fn test_thread_send() {
@@ -350,6 +307,20 @@ mod tests {
     fn test_feedback_loop() {
         let mut vec = vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26,
                            27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5];
-        println!("{}", threaded_phase_setter(vec, vec![9, 8, 7, 6, 5]));
+        assert_eq!(139629729, threaded_phase_setter(vec.clone(), vec![9, 8, 7, 6, 5]));
+        // any number of amplifiers
+        assert_eq!(threaded_phase_setter(vec.clone(), vec![9, 8, 7]), AmplifierChain::new(to_program(&vec)).feedback().signal(&[9, 8, 7]).unwrap() as i32);
+        assert_eq!(139629729, threaded_feedback_loop_phase_combinations(vec.clone(), vec![5, 6, 7, 8, 9]));
+        assert_eq!(139629729, phase_setter_feedback_loop(vec.clone(), vec![9, 8, 7, 6, 5]).unwrap());
+        assert_eq!(139629729, feedback_loop_phase_combinations(vec, vec![5, 6, 7, 8, 9]).unwrap());
+    }
+    #[test]
+    fn test_feedback_loop_matches_threaded() {
+        let vec = load_program("./inputs/day7").unwrap().iter().map(|value| *value as i32).collect::<Vec<_>>();
+        for p in Permutations::new(vec![5, 6, 7, 8, 9]) {
+            assert_eq!(threaded_phase_setter(vec.clone(), p.clone()), phase_setter_feedback_loop(vec.clone(), p.clone()).unwrap(), "{p:?}");
+        }
+        assert_eq!(threaded_feedback_loop_phase_combinations(vec.clone(), vec![5, 6, 7, 8, 9]),
+                   feedback_loop_phase_combinations(vec, vec![5, 6, 7, 8, 9]).unwrap());
     }
 }
@@ -1,7 +1,5 @@
-use std::time::Duration;
 use std::collections::BTreeSet;
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError, RecvTimeoutError};
-use std::thread;
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ParamMode {
@@ -82,7 +80,12 @@ fn parse_instruction(instruction: i64) -> (Opcode, [ParamMode; 3]) {
 }
 
 //Threaded
-pub fn computer_ver5(mut program: Vec<i64>, sender: SyncSender<i64>, receiver: Receiver<i64>) -> i64 {
+pub fn computer_ver5(program: Vec<i64>, sender: SyncSender<i64>, receiver: Receiver<i64>) -> i64 {
+    computer_ver5_with_memory(program, sender, receiver).0
+}
+
+// also returns the final memory, including the zeroed extension
+pub(crate) fn computer_ver5_with_memory(mut program: Vec<i64>, sender: SyncSender<i64>, receiver: Receiver<i64>) -> (i64, Vec<i64>) {
     let mut vec = program.clone();
     vec.append(&mut Vec::from([0; 16000]));
 
@@ -176,41 +179,9 @@ pub fn computer_ver5(mut program: Vec<i64>, sender: SyncSender<i64>, receiver: R
             }
         }
     }
-    result
+    (result, vec)
 }
 
-pub fn run_computer(vec: Vec<i64>, input: i64) -> i64 {
-    let (sender1, receiver2) = sync_channel(10);
-    let (sender2, receiver1) = sync_channel(10);
-
-    sender2.send(input).unwrap();
-    let handle = thread::spawn(|| { computer_ver5(vec, sender1, receiver1) });
-    // let output_res = match receiver2.recv_timeout(Duration::from_millis(5000)) {
-    //     Ok(val) => {val}
-    //     Err(e) => {eprintln!("{}", e); -1}
-    // };
-
-    let mut outputs = vec![];
-
-    let collector = thread::spawn(move || {
-        loop {
-            match receiver2.recv_timeout(Duration::from_millis(800)) {
-                Ok(val) => { outputs.push(val) }
-                Err(_) => { break }
-            }
-        }
-        outputs
-    });
-
-
-    let res = handle.join();
-    let output_res = collector.join().unwrap();
-    // println!("{:?}", res);
-    println!("{:?}", output_res);
-    res.unwrap()
-}
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -247,9 +218,4 @@ mod tests {
         println!("{:?}", output_res);
     }
     */
-    #[test]
-    fn test_run() {
-        let vec = create_vec();
-        println!("{}", run_computer(vec, 1));
-    }
 }
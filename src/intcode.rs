@@ -0,0 +1,231 @@
+use std::fs;
+use std::ops::Range;
+
+pub mod amplifier;
+pub mod analysis;
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod cli;
+pub mod compiled;
+pub mod debugger;
+#[cfg(test)]
+mod differential;
+pub mod disasm;
+pub mod dump;
+pub mod history;
+pub mod io;
+pub mod patch;
+pub mod profile;
+pub mod scheduler;
+pub mod search;
+pub mod selfmod;
+pub mod snapshot;
+pub mod symbolic;
+pub mod topology;
+pub mod trace;
+pub mod vm;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParamMode {
+    Positional,
+    Immediate,
+    Relative,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Sum = 1,
+    Multiply = 2,
+    Input = 3,
+    Output = 4,
+    JumpIfTrue = 5,
+    JumpIfFalse = 6,
+    LessThan = 7,
+    Equals = 8,
+    RelativeBaseOffset = 9,
+    Stop = 99,
+}
+
+impl TryFrom<u8> for ParamMode {
+    type Error = String;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Positional),
+            1 => Ok(Self::Immediate),
+            2 => Ok(Self::Relative),
+            _ => Err(format!("{} ParamMode not matched", value)),
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = String;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Sum),
+            2 => Ok(Self::Multiply),
+            3 => Ok(Self::Input),
+            4 => Ok(Self::Output),
+            5 => Ok(Self::JumpIfTrue),
+            6 => Ok(Self::JumpIfFalse),
+            7 => Ok(Self::LessThan),
+            8 => Ok(Self::Equals),
+            9 => Ok(Self::RelativeBaseOffset),
+            99 => Ok(Self::Stop),
+            _ => Err(format!("{} Opcode not matched", value)),
+        }
+    }
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Sum,
+        Opcode::Multiply,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::RelativeBaseOffset,
+        Opcode::Stop,
+    ];
+
+    // number of parameters following the opcode cell
+    pub fn arity(self) -> usize {
+        match self {
+            Opcode::Sum | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::RelativeBaseOffset => 1,
+            Opcode::Stop => 0,
+        }
+    }
+
+    // index of the parameter the instruction writes to, if any
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Opcode::Sum | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Opcode> {
+        Opcode::ALL.into_iter().find(|opcode| format!("{opcode:?}").eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [ParamMode; 3],
+}
+
+impl Instruction {
+    pub fn decode(value: i64) -> Result<Self, String> {
+        if !(0..100000).contains(&value) {
+            return Err(format!("{} is not a valid instruction", value));
+        }
+        let opcode = Opcode::try_from((value % 100) as u8)?;
+        let mode1 = ParamMode::try_from((value / 100 % 10) as u8)?;
+        let mode2 = ParamMode::try_from((value / 1000 % 10) as u8)?;
+        let mode3 = ParamMode::try_from((value / 10000 % 10) as u8)?;
+        Ok(Self { opcode, modes: [mode1, mode2, mode3] })
+    }
+
+    pub fn encode(&self) -> i64 {
+        let mut value = self.opcode as i64;
+        for (index, mode) in self.modes.iter().enumerate() {
+            value += *mode as i64 * 10_i64.pow(index as u32 + 2);
+        }
+        value
+    }
+
+    pub fn size(&self) -> usize {
+        self.opcode.arity() + 1
+    }
+
+    // true when the modes of the parameters the opcode doesn't take are left at zero,
+    // which is the only form the assembler can produce
+    pub fn is_canonical(&self) -> bool {
+        self.modes[self.opcode.arity()..].iter().all(|mode| *mode == ParamMode::Positional)
+    }
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<i64>, String> {
+    input
+        .split_terminator(&[',', '\n'][..])
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse::<i64>().map_err(|e| format!("{item:?}: {e}")))
+        .collect()
+}
+
+pub fn load_program(path: &str) -> Result<Vec<i64>, String> {
+    let input = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    parse_program(&input)
+}
+
+// input values given as separate command line arguments
+pub fn parse_inputs(args: &[String]) -> Result<Vec<i64>, String> {
+    args.iter().map(|arg| arg.trim().parse::<i64>().map_err(|_| format!("invalid input {arg:?}"))).collect()
+}
+
+pub fn to_intcode_string(program: &[i64]) -> String {
+    program.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",")
+}
+
+// `a..b`, `a..`, `..b` or a single `a`, as used by the command line tools
+pub fn parse_range(str: &str) -> Result<Range<usize>, String> {
+    let parse = |value: &str, default: usize| match value.trim() {
+        "" => Ok(default),
+        value => value.parse::<usize>().map_err(|_| format!("invalid range {str:?}")),
+    };
+    match str.split_once("..") {
+        Some((start, end)) => Ok(parse(start, 0)?..parse(end, usize::MAX)?),
+        None => {
+            let start = parse(str, 0)?;
+            Ok(start..start + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let instruction = Instruction::decode(1002).unwrap();
+        assert_eq!(instruction.opcode, Opcode::Multiply);
+        assert_eq!(instruction.modes, [ParamMode::Positional, ParamMode::Immediate, ParamMode::Positional]);
+        assert_eq!(instruction.encode(), 1002);
+        assert_eq!(Instruction::decode(21101).unwrap().encode(), 21101);
+        assert!(Instruction::decode(1303).is_err());
+        assert!(Instruction::decode(-1).is_err());
+    }
+
+    #[test]
+    fn test_parse_program() {
+        assert_eq!(parse_program("1,0,0,3,99\n").unwrap(), vec![1, 0, 0, 3, 99]);
+        assert_eq!(parse_program("104,-5, 99").unwrap(), vec![104, -5, 99]);
+        assert!(parse_program("1,x,2").is_err());
+    }
+
+    #[test]
+    fn test_parse_inputs() {
+        let args: Vec<String> = vec!["1".to_string(), "-20".to_string()];
+        assert_eq!(parse_inputs(&args).unwrap(), vec![1, -20]);
+        assert_eq!(parse_inputs(&["x".to_string()]).unwrap_err(), "invalid input \"x\"");
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("3..7").unwrap(), 3..7);
+        assert_eq!(parse_range("..7").unwrap(), 0..7);
+        assert_eq!(parse_range("3..").unwrap(), 3..usize::MAX);
+        assert_eq!(parse_range("5").unwrap(), 5..6);
+        assert!(parse_range("a..b").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+use std::thread;
+
+use crate::intcode::topology::Topology;
+use crate::intcode::vm::Vm;
+
+// Every ordering of `items`, one swap apart, generated lazily with Heap's algorithm so
+// only the current ordering is ever held.
+pub struct Permutations<T> {
+    items: Vec<T>,
+    counters: Vec<usize>,
+    index: usize,
+    started: bool,
+}
+
+impl<T: Clone> Permutations<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        let counters = vec![0; items.len()];
+        Self { items, counters, index: 1, started: false }
+    }
+}
+
+impl<T: Clone> Iterator for Permutations<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if !self.started {
+            self.started = true;
+            return Some(self.items.clone());
+        }
+        while self.index < self.items.len() {
+            if self.counters[self.index] < self.index {
+                let other = if self.index.is_multiple_of(2) { 0 } else { self.counters[self.index] };
+                self.items.swap(other, self.index);
+                self.counters[self.index] += 1;
+                self.index = 1;
+                return Some(self.items.clone());
+            }
+            self.counters[self.index] = 0;
+            self.index += 1;
+        }
+        None
+    }
+}
+
+// the highest signal seen so far and the phase order that produced it
+pub type Best = Option<(i64, Vec<i64>)>;
+
+// ties go to the smaller order, so the result doesn't depend on how the threads ran
+fn keep_best(best: &mut Best, signal: i64, order: Vec<i64>) {
+    if best.as_ref().is_none_or(|(best_signal, best_order)| signal > *best_signal || signal == *best_signal && order < *best_order) {
+        *best = Some((signal, order));
+    }
+}
+
+// A chain of amplifiers all running `program`, one per phase setting. The first reads its
+// phase and then 0, each later one its phase and then the previous one's output. With
+// feedback the last one's outputs go back to the first until they have all halted.
+//
+//   AmplifierChain::new(program).feedback().best(&[5, 6, 7, 8, 9])
+pub struct AmplifierChain {
+    program: Vec<i64>,
+    feedback: bool,
+    threads: usize,
+}
+
+impl AmplifierChain {
+    pub fn new(program: Vec<i64>) -> Self {
+        let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        Self { program, feedback: false, threads }
+    }
+
+    pub fn feedback(mut self) -> Self {
+        self.feedback = true;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // the signal that comes out of the last amplifier for one phase order
+    pub fn signal(&self, phases: &[i64]) -> Result<i64, String> {
+        if phases.is_empty() {
+            return Err("no amplifiers".to_string());
+        }
+        if !self.feedback {
+            let mut signal = 0;
+            for (stage, phase) in phases.iter().enumerate() {
+                let mut vm = Vm::with_inputs(self.program.clone(), &[*phase, signal]);
+                vm.run()?;
+                signal = *vm.outputs.last().ok_or_else(|| format!("amplifier {stage} produced no output"))?;
+            }
+            return Ok(signal);
+        }
+        let names: Vec<String> = (0..phases.len()).map(|stage| stage.to_string()).collect();
+        let mut topology = Topology::new();
+        for (stage, phase) in phases.iter().enumerate() {
+            let inputs = if stage == 0 { vec![*phase, 0] } else { vec![*phase] };
+            topology = topology.machine(&names[stage], self.program.clone(), &inputs);
+        }
+        let report = topology.ring(&names.iter().map(|name| name.as_str()).collect::<Vec<_>>()).run()?;
+        if !report.all_halted() {
+            return Err("amplifiers stopped without halting".to_string());
+        }
+        let last = report.get(&names[phases.len() - 1]).ok_or("missing last amplifier")?;
+        last.outputs.last().copied().ok_or("last amplifier produced no output".to_string())
+    }
+
+    // The highest signal over every order of `phases`, and the first order in lexicographic
+    // order to reach it. Orders are handed out to the threads one at a time.
+    pub fn best(&self, phases: &[i64]) -> Result<Best, String> {
+        let permutations = Mutex::new(Permutations::new(phases.to_vec()));
+        let results: Vec<Result<Best, String>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.threads).map(|_| scope.spawn(|| {
+                let mut best: Best = None;
+                loop {
+                    let Some(order) = permutations.lock().unwrap().next() else { return Ok(best) };
+                    let signal = self.signal(&order)?;
+                    keep_best(&mut best, signal, order);
+                }
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let mut best: Best = None;
+        for result in results {
+            if let Some((signal, order)) = result? {
+                keep_best(&mut best, signal, order);
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use crate::intcode::load_program;
+
+    fn create_vec() -> Vec<i64> {
+        vec![3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33,
+             1002, 33, 7, 33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_permutations() {
+        let all: Vec<Vec<i64>> = Permutations::new(vec![0, 1, 2, 3]).collect();
+        assert_eq!(all.len(), 24);
+        assert_eq!(all.iter().collect::<BTreeSet<_>>().len(), 24);
+        assert_eq!(all[0], vec![0, 1, 2, 3]);
+        assert_eq!(all[1], vec![1, 0, 2, 3]);
+        assert_eq!(Permutations::new(Vec::<i64>::new()).count(), 1);
+        assert_eq!(Permutations::new(vec![7]).collect::<Vec<_>>(), vec![vec![7]]);
+    }
+
+    #[test]
+    fn test_chain() {
+        let chain = AmplifierChain::new(create_vec());
+        assert_eq!(chain.signal(&[1, 0, 4, 3, 2]).unwrap(), 65210);
+        assert_eq!(chain.best(&[0, 1, 2, 3, 4]).unwrap(), Some((65210, vec![1, 0, 4, 3, 2])));
+        assert_eq!(chain.threads(1).best(&[0, 1, 2, 3, 4]).unwrap(), Some((65210, vec![1, 0, 4, 3, 2])));
+        assert!(AmplifierChain::new(vec![99]).best(&[0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_any_number_of_stages() {
+        // each stage multiplies the signal by 10 and adds its phase
+        let program = vec![3, 15, 3, 16, 1002, 16, 10, 16, 1, 15, 16, 16, 4, 16, 99, 0, 0];
+        let chain = AmplifierChain::new(program).threads(3);
+        assert_eq!(chain.best(&[3, 1, 2]).unwrap(), Some((321, vec![3, 2, 1])));
+        assert_eq!(chain.best(&[5, 7, 1, 9, 4, 2, 8]).unwrap(), Some((9875421, vec![9, 8, 7, 5, 4, 2, 1])));
+    }
+
+    #[test]
+    fn test_feedback() {
+        let program = vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26,
+                           27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5];
+        let chain = AmplifierChain::new(program).feedback();
+        assert_eq!(chain.signal(&[9, 8, 7, 6, 5]).unwrap(), 139629729);
+        assert_eq!(chain.best(&[5, 6, 7, 8, 9]).unwrap(), Some((139629729, vec![9, 8, 7, 6, 5])));
+
+        let chain = AmplifierChain::new(load_program("./inputs/day7").unwrap()).feedback();
+        let (signal, order) = chain.best(&[5, 6, 7, 8, 9]).unwrap().unwrap();
+        assert_eq!(chain.signal(&order).unwrap(), signal);
+        for other in Permutations::new(vec![5, 6, 7, 8, 9]) {
+            assert!(chain.signal(&other).unwrap() <= signal);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::intcode::cfg::{flow, Flow};
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::{load_program, Instruction, Opcode, ParamMode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellKind {
+    Data,
+    Opcode,
+    Operand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Warning {
+    ImmediateWrite { address: usize },
+    JumpToData { address: usize, target: i64 },
+    InvalidInstruction { address: usize },
+    // an instruction starting inside another one, e.g. a jump into the middle of it
+    Overlap { address: usize, other: usize },
+    Unreachable { start: usize, end: usize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ImmediateWrite { address } => write!(f, "{address}: writes through an immediate parameter"),
+            Warning::JumpToData { address, target } => write!(f, "{address}: jumps to {target}, which isn't an instruction"),
+            Warning::InvalidInstruction { address } => write!(f, "{address}: execution runs into a cell that isn't an instruction"),
+            Warning::Overlap { address, other } => write!(f, "{address}: instruction overlaps the one at {other}"),
+            Warning::Unreachable { start, end } => write!(f, "{start}..{end}: looks like code but is never reached"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    pub kinds: Vec<CellKind>,
+    // start and size of every instruction reached from address 0
+    pub instructions: BTreeMap<usize, usize>,
+    // jumps whose target is only known at run time
+    pub dynamic_jumps: Vec<usize>,
+    pub warnings: Vec<Warning>,
+}
+
+// Immediate operands pushed onto the stack, i.e. copied to a relative address by a Sum or
+// Multiply, the way a call passes its return address.
+fn pushed_operands(program: &[i64], address: usize) -> Vec<i64> {
+    let Ok(instruction) = Instruction::decode(program[address]) else { return vec![] };
+    if !matches!(instruction.opcode, Opcode::Sum | Opcode::Multiply) || instruction.modes[2] != ParamMode::Relative {
+        return vec![];
+    }
+    (0..2).filter(|index| instruction.modes[*index] == ParamMode::Immediate).map(|index| program[address + 1 + index]).collect()
+}
+
+// The longest run of at least two instructions from `start` that ends in a Stop or an
+// unconditional jump, which is how a function or the tail of a program looks. Data seldom
+// decodes that way, as it would need immediate modes in the right places.
+fn code_like(memory: &[i64], start: usize) -> Option<usize> {
+    let mut address = start;
+    let mut end = None;
+    let mut count = 0;
+    while let Some((_, size)) = disassemble_instruction(memory, address) {
+        let (flow, _) = flow(memory, address);
+        address += size;
+        count += 1;
+        if count >= 2 && matches!(flow, Flow::Stop | Flow::Jump { conditional: false, .. }) {
+            end = Some(address);
+        }
+    }
+    end
+}
+
+// Walks the code reachable from address 0 like `build_cfg` does, to tell instructions apart
+// from data without running anything. Jumps through memory are only followed when their
+// target looks like a return address: an immediate operand that points just past an
+// unconditional jump, pushed onto the stack by the usual call sequence.
+pub fn analyse(program: &[i64]) -> Analysis {
+    let mut instructions = BTreeMap::new();
+    let mut invalid = BTreeSet::new();
+    let mut dynamic_jumps = vec![];
+    let mut warnings = vec![];
+    // cells right after an unconditional jump, which only a call could return to
+    let mut returns = BTreeSet::new();
+    // (address, jump it was reached from)
+    let mut worklist: Vec<(usize, Option<usize>)> = vec![(0, None)];
+    loop {
+        while let Some((address, source)) = worklist.pop() {
+            if instructions.contains_key(&address) || invalid.contains(&address) {
+                continue;
+            }
+            let (flow, size) = if address < program.len() { flow(program, address) } else { (Flow::Invalid, 1) };
+            let next = address + size;
+            match flow {
+                Flow::Invalid => {
+                    invalid.insert(address);
+                    warnings.push(match source {
+                        Some(source) => Warning::JumpToData { address: source, target: address as i64 },
+                        None => Warning::InvalidInstruction { address },
+                    });
+                    continue;
+                }
+                Flow::Next => worklist.push((next, None)),
+                Flow::Jump { target, conditional } => {
+                    if conditional {
+                        worklist.push((next, None));
+                    } else {
+                        returns.insert(next);
+                    }
+                    match target {
+                        None => dynamic_jumps.push(address),
+                        Some(target) => match usize::try_from(target).ok().filter(|target| *target < program.len()) {
+                            Some(target) => worklist.push((target, Some(address))),
+                            None => warnings.push(Warning::JumpToData { address, target }),
+                        },
+                    }
+                }
+                Flow::Stop => {}
+            }
+            instructions.insert(address, size);
+            let instruction = Instruction::decode(program[address]).unwrap();
+            if instruction.opcode.write_param().is_some_and(|index| instruction.modes[index] == ParamMode::Immediate) {
+                warnings.push(Warning::ImmediateWrite { address });
+            }
+        }
+        if dynamic_jumps.is_empty() {
+            break;
+        }
+        let pointers: BTreeSet<usize> = instructions.keys()
+            .flat_map(|address| pushed_operands(program, *address))
+            .filter_map(|value| usize::try_from(value).ok())
+            .filter(|pointer| returns.contains(pointer) && *pointer < program.len() && !instructions.contains_key(pointer))
+            .filter(|pointer| !matches!(flow(program, *pointer).0, Flow::Invalid))
+            .collect();
+        if pointers.is_empty() {
+            break;
+        }
+        worklist.extend(pointers.into_iter().map(|pointer| (pointer, None)));
+    }
+
+    let mut kinds = vec![CellKind::Data; program.len()];
+    let mut previous: Option<(usize, usize)> = None;
+    for (&address, &size) in &instructions {
+        if let Some((other, end)) = previous.filter(|(_, end)| address < *end) {
+            warnings.push(Warning::Overlap { address, other });
+            previous = Some((address, end.max(address + size)));
+        } else {
+            previous = Some((address, address + size));
+        }
+        kinds[address] = CellKind::Opcode;
+        for kind in &mut kinds[address + 1..address + size] {
+            if *kind == CellKind::Data {
+                *kind = CellKind::Operand;
+            }
+        }
+    }
+
+    let mut start = 0;
+    while start < program.len() {
+        let end = (start..program.len()).find(|address| kinds[*address] != CellKind::Data).unwrap_or(program.len());
+        let mut address = start;
+        while address < end {
+            match code_like(&program[..end], address) {
+                Some(code_end) => {
+                    warnings.push(Warning::Unreachable { start: address, end: code_end });
+                    address = code_end;
+                }
+                None => address += 1,
+            }
+        }
+        start = end + 1;
+    }
+    warnings.sort_by_key(|warning| match warning {
+        Warning::ImmediateWrite { address }
+        | Warning::JumpToData { address, .. }
+        | Warning::InvalidInstruction { address }
+        | Warning::Overlap { address, .. } => *address,
+        Warning::Unreachable { start, .. } => *start,
+    });
+    Analysis { kinds, instructions, dynamic_jumps, warnings }
+}
+
+impl Analysis {
+    pub fn report(&self) -> String {
+        let code = self.kinds.iter().filter(|kind| **kind != CellKind::Data).count();
+        let mut lines = vec![format!(
+            "{} instructions in {} cells, {} data cells, {} dynamic jumps",
+            self.instructions.len(), code, self.kinds.len() - code, self.dynamic_jumps.len()
+        )];
+        if self.warnings.is_empty() {
+            lines.push("no warnings".to_string());
+        }
+        lines.extend(self.warnings.iter().map(|warning| format!("warning: {warning}")));
+        lines.join("\n")
+    }
+}
+
+// `analyse <program file> [--dis]`
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: analyse <program file> [--dis]");
+        return;
+    };
+    match load_program(path) {
+        Ok(program) => {
+            let analysis = analyse(&program);
+            println!("{}", analysis.report());
+            if args.iter().any(|arg| arg == "--dis") {
+                print!("\n{}", crate::intcode::disasm::disassemble_analysed(&program, &analysis));
+            }
+        }
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_calls_and_data() {
+        let program = assemble("
+                Sum back, 0, [rb+1]
+                JumpIfTrue 1, double
+        back:   Output [value]
+                Stop
+        double: Multiply [value], 2, [value]
+                JumpIfTrue 1, [rb+1]
+        value:  .data 21
+        ").unwrap();
+        let analysis = analyse(&program);
+        assert_eq!(analysis.instructions.keys().copied().collect::<Vec<_>>(), vec![0, 4, 7, 9, 10, 14]);
+        assert_eq!(analysis.dynamic_jumps, vec![14]);
+        assert_eq!(analysis.kinds[0], CellKind::Opcode);
+        assert_eq!(analysis.kinds[1], CellKind::Operand);
+        assert_eq!(analysis.kinds[17], CellKind::Data);
+        assert_eq!(analysis.warnings, vec![]);
+        assert!(analysis.report().ends_with("no warnings"));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let program = assemble("
+                Sum 1, 1, 2
+                JumpIfTrue 1, data
+        data:   .data 0
+        dead:   Output 1
+                Stop
+        ").unwrap();
+        let analysis = analyse(&program);
+        assert_eq!(analysis.warnings, vec![
+            Warning::ImmediateWrite { address: 0 },
+            Warning::JumpToData { address: 4, target: 7 },
+            Warning::Unreachable { start: 8, end: 11 },
+        ]);
+        assert!(analysis.report().contains("warning: 4: jumps to 7, which isn't an instruction"));
+
+        // jumping to the operand at 1, which decodes as `Sum [1], [0], [0]`
+        let analysis = analyse(&[1105, 1, 1, 0, 0, 99]);
+        assert_eq!(analysis.warnings, vec![Warning::Overlap { address: 1, other: 0 }]);
+        assert_eq!(analyse(&[1, 0, 0, 0]).warnings, vec![Warning::InvalidInstruction { address: 4 }]);
+        assert_eq!(analyse(&[1105, 1, 40]).warnings, vec![Warning::JumpToData { address: 0, target: 40 }]);
+    }
+
+    #[test]
+    fn test_inputs() {
+        for day in ["day2", "day9", "day13"] {
+            let program = load_program(&format!("./inputs/{day}")).unwrap();
+            let analysis = analyse(&program);
+            assert!(!analysis.warnings.iter().any(|warning| matches!(warning, Warning::InvalidInstruction { .. } | Warning::JumpToData { .. })), "{day}: {}", analysis.report());
+        }
+        // day5 patches the instruction at 6 with its first input
+        let analysis = analyse(&load_program("./inputs/day5").unwrap());
+        assert_eq!(analysis.warnings[0], Warning::InvalidInstruction { address: 6 });
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::intcode::io::{read_line, run_io, Input, Output};
+use crate::intcode::load_program;
+use crate::intcode::vm::{Status, Vm};
+
+// Output values past this aren't characters but numbers the program reports, like the
+// amount of dust collected or the hull damage.
+const MAX_ASCII: i64 = 127;
+
+// a line of text as the program expects it, one character per value and a trailing newline
+pub fn encode_line(line: &str) -> Result<Vec<i64>, String> {
+    line.chars().chain(['\n']).map(|c| {
+        if c.is_ascii() { Ok(c as i64) } else { Err(format!("non-ASCII character {c:?} in input {line:?}")) }
+    }).collect()
+}
+
+// Feeds lines of text to a program, spelled out a character at a time. A terminal input
+// prompts for more lines once the queued ones run out.
+#[derive(Clone, Debug, Default)]
+pub struct AsciiInput {
+    lines: VecDeque<String>,
+    pending: VecDeque<i64>,
+    terminal: bool,
+    prompt: Option<String>,
+}
+
+impl AsciiInput {
+    pub fn new(lines: &[&str]) -> Self {
+        Self { lines: lines.iter().map(|line| line.to_string()).collect(), ..Self::default() }
+    }
+
+    pub fn terminal(prompt: Option<&str>) -> Self {
+        Self { terminal: true, prompt: prompt.map(str::to_string), ..Self::default() }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        self.lines.push_back(line.to_string());
+    }
+
+    fn next_line(&mut self) -> Result<Option<String>, String> {
+        match self.lines.pop_front() {
+            Some(line) => Ok(Some(line)),
+            None if self.terminal => read_line(self.prompt.as_deref()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Input for AsciiInput {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        while self.pending.is_empty() {
+            let Some(line) = self.next_line()? else { return Ok(None) };
+            self.pending.extend(encode_line(&line)?);
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+// Collects a program's output as text lines, with any value that isn't ASCII kept apart
+// as a result. With `echo` set, lines and results are printed as soon as they complete.
+#[derive(Clone, Debug, Default)]
+pub struct AsciiOutput {
+    pub lines: Vec<String>,
+    pub results: Vec<i64>,
+    partial: String,
+    echo: bool,
+}
+
+impl AsciiOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn echo(mut self) -> Self {
+        self.echo = true;
+        self
+    }
+
+    // whatever was printed since the last newline
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+}
+
+impl Output for AsciiOutput {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        match value {
+            10 => {
+                let line = mem::take(&mut self.partial);
+                if self.echo {
+                    println!("{line}");
+                }
+                self.lines.push(line);
+            }
+            0..=MAX_ASCII => self.partial.push(value as u8 as char),
+            _ => {
+                if self.echo {
+                    println!("{value}");
+                }
+                self.results.push(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+// runs `vm` on `lines` of input until it halts or wants more, returning what it printed
+pub fn run_ascii(vm: &mut Vm, lines: &[&str]) -> Result<(AsciiOutput, Status), String> {
+    let mut output = AsciiOutput::new();
+    let status = run_io(vm, &mut AsciiInput::new(lines), &mut output)?;
+    Ok((output, status))
+}
+
+// `ascii <program file>`, talks to the program line by line on the terminal
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: ascii <program file>");
+        return;
+    };
+    let result = load_program(path).and_then(|program| {
+        let mut output = AsciiOutput::new().echo();
+        let status = run_io(&mut Vm::new(program), &mut AsciiInput::terminal(None), &mut output)?;
+        if !output.partial().is_empty() {
+            println!("{}", output.partial());
+        }
+        Ok(status)
+    });
+    match result {
+        Ok(status) => println!("{status:?}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn create_program() -> Vec<i64> {
+        // echoes every line back, then reports its length plus 1000
+        assemble("
+        loop:   Input [c]
+                Equals [c], 10, [t]
+                JumpIfTrue [t], done
+                Output [c]
+                Sum [n], 1, [n]
+                JumpIfTrue 1, loop
+        done:   Output 10
+                Output [n]
+                Sum 1000, 0, [n]
+                JumpIfTrue 1, loop
+        c:      .data 0
+        t:      .data 0
+        n:      .data 1000
+        ").unwrap()
+    }
+
+    #[test]
+    fn test_lines_and_results() {
+        let mut vm = Vm::new(create_program());
+        let (output, status) = run_ascii(&mut vm, &["hello", "", "intcode"]).unwrap();
+        assert_eq!(status, Status::NeedsInput);
+        assert_eq!(output.lines, vec!["hello", "", "intcode"]);
+        assert_eq!(output.results, vec![1005, 1000, 1007]);
+        assert_eq!(output.partial(), "");
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(encode_line("AB").unwrap(), vec![65, 66, 10]);
+        assert!(encode_line("café").unwrap_err().contains("non-ASCII"));
+        assert!(run_ascii(&mut Vm::new(create_program()), &["π"]).is_err());
+    }
+}
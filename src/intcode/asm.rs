@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use crate::intcode::{Instruction, Opcode, ParamMode};
+
+// Source format, one statement per line, `;` starts a comment:
+//
+//   loop:   Sum [count], -1, [count]      ; [addr] positional, bare value immediate
+//           JumpIfTrue [count], loop      ; labels resolve to their address
+//           Output [rb-1]                 ; [rb], [rb+n], [rb-n] relative
+//           Stop
+//   count:  .data 5, loop+1               ; literal cells
+//   buffer: .reserve 8                    ; zero filled cells
+//
+// Mnemonics are the `Opcode` variant names, matched case-insensitively.
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(i64),
+    Label(String, i64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Operand {
+    mode: ParamMode,
+    value: Expr,
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Instruction(Opcode, Vec<Operand>),
+    Data(Vec<Expr>),
+    Reserve(usize),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(opcode, _) => opcode.arity() + 1,
+            Statement::Data(values) => values.len(),
+            Statement::Reserve(count) => *count,
+        }
+    }
+}
+
+fn is_identifier(str: &str) -> bool {
+    let mut chars = str.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_expr(str: &str) -> Result<Expr, String> {
+    let str = str.trim();
+    if let Ok(number) = str.parse::<i64>() {
+        return Ok(Expr::Number(number));
+    }
+    let (label, offset) = match str.find(['+', '-']) {
+        Some(index) => {
+            let offset = str[index..].replace(' ', "").trim_start_matches('+').parse::<i64>()
+                .map_err(|_| format!("invalid offset in {str:?}"))?;
+            (str[..index].trim(), offset)
+        }
+        None => (str, 0),
+    };
+    if !is_identifier(label) || label.eq_ignore_ascii_case("rb") {
+        return Err(format!("invalid expression {str:?}"));
+    }
+    Ok(Expr::Label(label.to_string(), offset))
+}
+
+fn parse_operand(str: &str) -> Result<Operand, String> {
+    let str = str.trim();
+    match str.strip_prefix('[').and_then(|inner| inner.strip_suffix(']')) {
+        Some(inner) => {
+            let inner = inner.trim();
+            let is_relative = inner.get(..2).is_some_and(|prefix| prefix.eq_ignore_ascii_case("rb"))
+                && !inner[2..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+            if is_relative {
+                let offset = inner[2..].trim();
+                let value = if offset.is_empty() {
+                    Expr::Number(0)
+                } else if let Some(rest) = offset.strip_prefix('+') {
+                    parse_expr(rest)?
+                } else if offset.starts_with('-') {
+                    parse_expr(offset)?
+                } else {
+                    return Err(format!("invalid relative operand {str:?}"));
+                };
+                Ok(Operand { mode: ParamMode::Relative, value })
+            } else {
+                Ok(Operand { mode: ParamMode::Positional, value: parse_expr(inner)? })
+            }
+        }
+        None => Ok(Operand { mode: ParamMode::Immediate, value: parse_expr(str)? }),
+    }
+}
+
+fn parse_list(str: &str) -> Vec<&str> {
+    if str.trim().is_empty() {
+        vec![]
+    } else {
+        str.split(',').map(|item| item.trim()).collect()
+    }
+}
+
+fn parse_statement(str: &str) -> Result<Statement, String> {
+    let (head, rest) = match str.find(char::is_whitespace) {
+        Some(index) => (&str[..index], &str[index..]),
+        None => (str, ""),
+    };
+    match head {
+        ".data" => {
+            let values = parse_list(rest).into_iter().map(parse_expr).collect::<Result<Vec<_>, _>>()?;
+            if values.is_empty() {
+                return Err(".data needs at least one value".to_string());
+            }
+            Ok(Statement::Data(values))
+        }
+        ".reserve" => {
+            let count = rest.trim().parse::<usize>().map_err(|_| format!("invalid .reserve count {:?}", rest.trim()))?;
+            Ok(Statement::Reserve(count))
+        }
+        _ if head.starts_with('.') => Err(format!("unknown directive {head}")),
+        _ => {
+            let opcode = Opcode::from_name(head).ok_or_else(|| format!("unknown mnemonic {head}"))?;
+            let operands = parse_list(rest).into_iter().map(parse_operand).collect::<Result<Vec<_>, _>>()?;
+            if operands.len() != opcode.arity() {
+                return Err(format!("{opcode:?} takes {} operands, got {}", opcode.arity(), operands.len()));
+            }
+            Ok(Statement::Instruction(opcode, operands))
+        }
+    }
+}
+
+fn resolve(expr: &Expr, labels: &HashMap<String, usize>) -> Result<i64, String> {
+    match expr {
+        Expr::Number(number) => Ok(*number),
+        Expr::Label(label, offset) => match labels.get(label) {
+            Some(address) => Ok(*address as i64 + offset),
+            None => Err(format!("undefined label {label}")),
+        },
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, String> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut address = 0;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_error = |e: String| format!("line {}: {}", line_index + 1, e);
+        let mut line = line.split(';').next().unwrap().trim();
+
+        while let Some(index) = line.find(':') {
+            let label = line[..index].trim();
+            if !is_identifier(label) || label.eq_ignore_ascii_case("rb") {
+                return Err(line_error(format!("invalid label {label:?}")));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(line_error(format!("duplicate label {label}")));
+            }
+            line = line[index + 1..].trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line).map_err(line_error)?;
+        address += statement.size();
+        statements.push((line_index, statement));
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for (line_index, statement) in statements {
+        let line_error = |e: String| format!("line {}: {}", line_index + 1, e);
+        match statement {
+            Statement::Instruction(opcode, operands) => {
+                let mut modes = [ParamMode::Positional; 3];
+                for (index, operand) in operands.iter().enumerate() {
+                    modes[index] = operand.mode;
+                }
+                program.push(Instruction { opcode, modes }.encode());
+                for operand in operands {
+                    program.push(resolve(&operand.value, &labels).map_err(line_error)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(resolve(&value, &labels).map_err(line_error)?);
+                }
+            }
+            Statement::Reserve(count) => program.resize(program.len() + count, 0),
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disasm::disassemble;
+
+    fn create_source() -> &'static str {
+        "
+        ; day7 feedback loop amplifier
+                Input [phase]
+                Sum [phase], -4, [phase]
+        loop:   Input [signal]
+                Multiply [signal], 2, [signal]
+                Sum [signal], [phase], [signal]
+                Output [signal]
+                Sum [count], -1, [count]
+                JumpIfTrue [count], loop
+                Stop
+        phase:  .data 0
+        signal: .data 0
+        count:  .data 5
+        "
+    }
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(create_source()).unwrap();
+        assert_eq!(program, vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26,
+                                 27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_relative_and_directives() {
+        let source = "
+            RelativeBaseOffset table+1
+            output [rb-1]
+            OUTPUT [rb]
+            Equals [rb+1], 7, [buffer+2]
+            stop
+        table: .data 11, 22, table
+        buffer: .reserve 3
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(program, vec![109, 12, 204, -1, 204, 0, 1208, 1, 7, 16, 99, 11, 22, 11, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(assemble("Jump 1, 2").unwrap_err().contains("unknown mnemonic"));
+        assert!(assemble("Sum 1, 2").unwrap_err().contains("takes 3 operands"));
+        assert!(assemble("Output [nowhere]").unwrap_err().contains("undefined label"));
+        assert!(assemble("a: Stop\na: Stop").unwrap_err().starts_with("line 2"));
+        assert!(assemble(".org 5").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = assemble(create_source()).unwrap();
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+
+        let quine = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+        assert_eq!(assemble(&disassemble(&quine)).unwrap(), quine);
+        let odd = vec![1104, 5, 2, 7, 203, -3, 99999];
+        assert_eq!(assemble(&disassemble(&odd)).unwrap(), odd);
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::{load_program, Instruction, Opcode, ParamMode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Fallthrough,
+    Jump,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    // addresses of the instructions in the block, in order
+    pub instructions: Vec<usize>,
+    pub successors: Vec<(usize, Edge)>,
+    // ends in a jump whose target is only known at run time
+    pub dynamic_jump: bool,
+    // runs into a cell that doesn't decode, or off the end of the program
+    pub invalid_end: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+}
+
+pub(crate) enum Flow {
+    Next,
+    Jump { target: Option<i64>, conditional: bool },
+    Stop,
+    Invalid,
+}
+
+pub(crate) fn flow(program: &[i64], address: usize) -> (Flow, usize) {
+    let Ok(instruction) = Instruction::decode(program[address]) else { return (Flow::Invalid, 1) };
+    if address + instruction.size() > program.len() {
+        return (Flow::Invalid, 1);
+    }
+    let flow = match instruction.opcode {
+        Opcode::Stop => Flow::Stop,
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let target = (instruction.modes[1] == ParamMode::Immediate).then(|| program[address + 2]);
+            match instruction.modes[0] {
+                // an immediate condition always or never jumps
+                ParamMode::Immediate if (program[address + 1] != 0) == (instruction.opcode == Opcode::JumpIfTrue) => {
+                    Flow::Jump { target, conditional: false }
+                }
+                ParamMode::Immediate => Flow::Next,
+                _ => Flow::Jump { target, conditional: true },
+            }
+        }
+        _ => Flow::Next,
+    };
+    (flow, instruction.size())
+}
+
+// Follows execution from address 0 along fallthroughs and immediate jump targets, splitting
+// the reachable code into basic blocks. Code reached only through dynamic jumps, and any
+// effect of the program rewriting itself, is invisible to this analysis.
+pub fn build_cfg(program: &[i64]) -> ControlFlowGraph {
+    let mut sizes = BTreeMap::new();
+    let mut leaders = BTreeSet::from([0]);
+    let mut worklist = vec![0];
+    while let Some(address) = worklist.pop() {
+        if address >= program.len() || sizes.contains_key(&address) {
+            continue;
+        }
+        let (flow, size) = flow(program, address);
+        sizes.insert(address, size);
+        match flow {
+            Flow::Next => worklist.push(address + size),
+            Flow::Jump { target, conditional } => {
+                if conditional {
+                    leaders.insert(address + size);
+                    worklist.push(address + size);
+                }
+                if let Some(target) = target.and_then(|target| usize::try_from(target).ok()) {
+                    leaders.insert(target);
+                    worklist.push(target);
+                }
+            }
+            Flow::Stop | Flow::Invalid => {}
+        }
+    }
+
+    let mut graph = ControlFlowGraph::default();
+    for &start in leaders.iter().filter(|leader| sizes.contains_key(leader)) {
+        let mut block = BasicBlock { start, instructions: vec![], successors: vec![], dynamic_jump: false, invalid_end: false };
+        let mut address = start;
+        loop {
+            block.instructions.push(address);
+            let (flow, size) = flow(program, address);
+            let next = address + size;
+            match flow {
+                Flow::Next if leaders.contains(&next) => block.successors.push((next, Edge::Fallthrough)),
+                Flow::Next if next < program.len() => {
+                    address = next;
+                    continue;
+                }
+                Flow::Next | Flow::Invalid => block.invalid_end = true,
+                Flow::Jump { target, conditional } => {
+                    match target.and_then(|target| usize::try_from(target).ok()).filter(|target| *target < program.len()) {
+                        Some(target) => block.successors.push((target, Edge::Jump)),
+                        None if target.is_some() => block.invalid_end = true,
+                        None => block.dynamic_jump = true,
+                    }
+                    if conditional {
+                        if next < program.len() {
+                            block.successors.push((next, Edge::Fallthrough));
+                        } else {
+                            block.invalid_end = true;
+                        }
+                    }
+                }
+                Flow::Stop => {}
+            }
+            break;
+        }
+        graph.blocks.insert(start, block);
+    }
+    graph
+}
+
+impl ControlFlowGraph {
+    pub fn to_dot(&self, program: &[i64]) -> String {
+        let mut lines = vec![
+            "digraph intcode {".to_string(),
+            "    node [shape=box, fontname=\"monospace\"];".to_string(),
+        ];
+        for block in self.blocks.values() {
+            let label: String = block.instructions.iter().map(|address| {
+                let text = disassemble_instruction(program, *address).map(|(text, _)| text)
+                    .unwrap_or_else(|| format!(".data {}", program[*address]));
+                format!("{address}: {text}\\l")
+            }).collect();
+            lines.push(format!("    b{} [label=\"{}\"];", block.start, label));
+            for (target, edge) in &block.successors {
+                let style = match edge {
+                    Edge::Jump => "",
+                    Edge::Fallthrough => " [style=dashed]",
+                };
+                lines.push(format!("    b{} -> b{}{};", block.start, target, style));
+            }
+            if block.dynamic_jump {
+                lines.push(format!("    b{} -> dynamic [style=dotted];", block.start));
+            }
+            if block.invalid_end {
+                lines.push(format!("    b{} -> invalid [style=dotted];", block.start));
+            }
+        }
+        if self.blocks.values().any(|block| block.dynamic_jump) {
+            lines.push("    dynamic [shape=diamond, label=\"dynamic jump\"];".to_string());
+        }
+        if self.blocks.values().any(|block| block.invalid_end) {
+            lines.push("    invalid [shape=octagon, label=\"invalid code\"];".to_string());
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+// `cfg <program file>`, prints the graph in Graphviz DOT format
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: cfg <program file>");
+        return;
+    };
+    match load_program(path) {
+        Ok(program) => println!("{}", build_cfg(&program).to_dot(&program)),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn create_program() -> Vec<i64> {
+        assemble("
+                Input [n]
+        loop:   Output [n]
+                Sum [n], -1, [n]
+                JumpIfTrue [n], loop
+                JumpIfFalse 0, done
+                Stop
+        done:   RelativeBaseOffset 1
+                JumpIfTrue 1, [rb]
+        n:      .data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn test_blocks() {
+        let program = create_program();
+        let graph = build_cfg(&program);
+        assert_eq!(graph.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 2, 11, 15]);
+        assert_eq!(graph.blocks[&0].successors, vec![(2, Edge::Fallthrough)]);
+        assert_eq!(graph.blocks[&2].instructions, vec![2, 4, 8]);
+        assert_eq!(graph.blocks[&2].successors, vec![(2, Edge::Jump), (11, Edge::Fallthrough)]);
+        // the immediate false condition always jumps, so the Stop after it is unreachable
+        assert_eq!(graph.blocks[&11].successors, vec![(15, Edge::Jump)]);
+        assert_eq!(graph.blocks[&15].instructions, vec![15, 17]);
+        assert!(graph.blocks[&15].dynamic_jump);
+        assert!(graph.blocks[&15].successors.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_and_dot() {
+        let program = vec![1105, 1, 4, 99, 42];
+        let graph = build_cfg(&program);
+        assert!(graph.blocks[&4].invalid_end);
+        assert!(!graph.blocks.contains_key(&3));
+
+        let dot = build_cfg(&create_program()).to_dot(&create_program());
+        assert!(dot.starts_with("digraph intcode {"));
+        assert!(dot.contains("b2 [label=\"2: Output [20]\\l4: Sum [20], -1, [20]\\l8: JumpIfTrue [20], 2\\l\"];"));
+        assert!(dot.contains("b2 -> b11 [style=dashed];"));
+        assert!(dot.contains("b15 -> dynamic [style=dotted];"));
+        assert!(dot.ends_with("}"));
+    }
+}
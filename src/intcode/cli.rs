@@ -0,0 +1,307 @@
+use crate::intcode::ascii::{encode_line, AsciiInput, AsciiOutput};
+use crate::intcode::io::{FileInput, Input, Output, StdinInput, StdoutOutput};
+use crate::intcode::patch::{self, PatchSet};
+use crate::intcode::load_program;
+use crate::intcode::profile::Profiler;
+use crate::intcode::trace::Tracer;
+use crate::intcode::vm::{Limits, Observer, Status, Step, Vm};
+
+const USAGE: &str = "\
+usage: intcode <program file> [option ...] [input ...]
+  --input v1,v2,...   queue input values
+  --input-file file   queue the values in a file, separated by commas or newlines
+  --line text         queue a line of text as ASCII codes
+  --set addr=value    patch memory before running
+  --patch file:name   apply a named patch set from a file
+  --print addr        show a memory cell after the run
+  --ascii             print output as text, with values above 127 on lines of their own
+  --stdin             read more input from the terminal when it runs out
+  --trace file        record every executed instruction
+  --profile           print execution counts after the run
+  --max-steps n       give up after n instructions
+  --detect-loops      give up when the program can only repeat itself";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+    pub patches: Vec<(usize, i64)>,
+    pub print: Vec<usize>,
+    pub ascii: bool,
+    pub stdin: bool,
+    pub trace: Option<String>,
+    pub profile: bool,
+    pub max_steps: Option<u64>,
+    pub detect_loops: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub status: Status,
+    pub steps: u64,
+    pub cells: Vec<(usize, i64)>,
+    pub profile: Option<String>,
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+    value.trim().parse::<T>().map_err(|_| format!("invalid {what} {value:?}"))
+}
+
+// Input options are queued in the order they are given, so `--line` can follow numbers.
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let path = args.first().ok_or(USAGE)?;
+    let mut options = Options { program: load_program(path)?, ..Options::default() };
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(|value| value.as_str()).ok_or_else(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--input" => {
+                for item in value()?.split(',').filter(|item| !item.trim().is_empty()) {
+                    options.inputs.push(parse_number(item, "input")?);
+                }
+            }
+            "--input-file" => {
+                let mut file = FileInput::open(value()?)?;
+                while let Some(value) = file.read()? {
+                    options.inputs.push(value);
+                }
+            }
+            "--line" => options.inputs.extend(encode_line(value()?)?),
+            "--set" => {
+                let patch = value()?;
+                let (address, value) = patch.split_once('=').ok_or_else(|| format!("invalid patch {patch:?}, expected addr=value"))?;
+                let set = PatchSet::new(&format!("--set {patch}")).set(parse_number(address, "address")?, parse_number(value, "value")?);
+                set.validate(&options.program)?;
+                options.patches.extend(set.patches);
+            }
+            "--patch" => {
+                let set = patch::load_set(value()?)?;
+                set.validate(&options.program)?;
+                options.patches.extend(set.patches);
+            }
+            "--print" => options.print.push(parse_number(value()?, "address")?),
+            "--ascii" => options.ascii = true,
+            "--stdin" => options.stdin = true,
+            "--trace" => options.trace = Some(value()?.to_string()),
+            "--profile" => options.profile = true,
+            "--max-steps" => options.max_steps = Some(parse_number(value()?, "step count")?),
+            "--detect-loops" => options.detect_loops = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n{USAGE}")),
+            _ => options.inputs.push(parse_number(arg, "input")?),
+        }
+    }
+    Ok(options)
+}
+
+// Hands every output on as soon as it is produced, and keeps count for the summary.
+struct Session<'a, O: Output> {
+    output: &'a mut O,
+    terminal: Option<Box<dyn Input>>,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    steps: u64,
+}
+
+impl<O: Output> Observer for Session<'_, O> {
+    fn step(&mut self, step: &Step) -> Result<(), String> {
+        self.steps += 1;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(step);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(*step)?;
+        }
+        match step.output {
+            Some(value) => self.output.write(value),
+            None => Ok(()),
+        }
+    }
+
+    fn needs_input(&mut self) -> Result<Option<i64>, String> {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.needs_input()?;
+        }
+        Ok(self.terminal.as_mut().map(|terminal| terminal.read()).transpose()?.flatten())
+    }
+}
+
+// Runs the program, handing every output to `output` as soon as it is produced. Without
+// `stdin` the run stops at the first input it wasn't given.
+pub fn run(options: &Options, output: &mut impl Output) -> Result<Summary, String> {
+    let mut vm = Vm::with_inputs(options.program.clone(), &options.inputs);
+    for (address, value) in &options.patches {
+        vm.write(*address, *value)?;
+    }
+    let mut session = Session {
+        output,
+        terminal: options.stdin.then(|| -> Box<dyn Input> {
+            if options.ascii { Box::new(AsciiInput::terminal(None)) } else { Box::new(StdinInput { prompt: None }) }
+        }),
+        tracer: options.trace.as_deref().map(Tracer::create).transpose()?,
+        profiler: options.profile.then(Profiler::default),
+        steps: 0,
+    };
+    let limits = Limits { max_steps: options.max_steps, detect_loops: options.detect_loops };
+    let status = vm.run_observed(limits, &mut session)?;
+    if let Some(tracer) = session.tracer {
+        tracer.finish()?;
+    }
+    Ok(Summary {
+        status,
+        steps: session.steps,
+        cells: options.print.iter().map(|address| (*address, vm.read(*address))).collect(),
+        profile: session.profiler.map(|profiler| profiler.report(&vm, 20)),
+    })
+}
+
+// `intcode <program file> [option ...] [input ...]`
+pub fn main(args: &[String]) {
+    let result = parse_args(args).and_then(|options| {
+        if options.ascii {
+            let mut output = AsciiOutput::new().echo();
+            let summary = run(&options, &mut output)?;
+            if !output.partial().is_empty() {
+                println!("{}", output.partial());
+            }
+            Ok(summary)
+        } else {
+            run(&options, &mut StdoutOutput)
+        }
+    });
+    match result {
+        Ok(summary) => {
+            for (address, value) in &summary.cells {
+                println!("[{address}] = {value}");
+            }
+            if let Some(profile) = &summary.profile {
+                println!("\n{profile}");
+            }
+            eprintln!("{:?} after {} steps", summary.status, summary.steps);
+        }
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::{env, fs};
+    use crate::day2;
+
+    fn create_vec() -> Vec<i64> {
+        // a quine
+        vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    // tests run in parallel, possibly in several processes at once
+    fn temp_path(name: &str) -> String {
+        env::temp_dir().join(format!("intcode_cli_{name}_{}", std::process::id())).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let input_file = temp_path("inputs");
+        fs::write(&input_file, "5\n6\n").unwrap();
+        let options = parse_args(&args(&[
+            "./inputs/day2", "--set", "1=12", "--set", "2=2", "--print", "0", "1",
+            "--input", "2,3", "--input-file", &input_file, "--line", "hi", "--max-steps", "100", "--detect-loops",
+        ]));
+        fs::remove_file(&input_file).unwrap();
+        let options = options.unwrap();
+        assert_eq!(options.program[..4], [1, 0, 0, 3]);
+        assert_eq!(options.patches, vec![(1, 12), (2, 2)]);
+        assert_eq!(options.print, vec![0]);
+        assert_eq!(options.inputs, vec![1, 2, 3, 5, 6, 104, 105, 10]);
+        assert_eq!(options.max_steps, Some(100));
+        assert!(options.detect_loops);
+
+        let far = parse_args(&args(&["./inputs/day2", "--set", "1000=1"])).unwrap_err();
+        assert!(far.starts_with("patch set --set 1000=1: address 1000 is outside the program"), "{far}");
+
+        assert!(parse_args(&args(&["./inputs/day2", "--set", "1"])).unwrap_err().starts_with("invalid patch"));
+        assert_eq!(parse_args(&args(&["./inputs/day2", "--print"])).unwrap_err(), "missing value for --print");
+        assert!(parse_args(&args(&["./inputs/day2", "--fast"])).unwrap_err().starts_with("unknown option --fast"));
+        assert!(parse_args(&args(&[])).unwrap_err().starts_with("usage"));
+    }
+
+    #[test]
+    fn test_run() {
+        let options = Options { program: create_vec(), ..Options::default() };
+        let mut outputs = vec![];
+        let summary = run(&options, &mut outputs).unwrap();
+        assert_eq!(outputs, create_vec());
+        assert_eq!(summary.status, Status::Halted);
+
+        let options = Options { max_steps: Some(10), ..options };
+        assert_eq!(run(&options, &mut vec![]).unwrap_err(), "step limit of 10 reached at ip 0");
+
+        let options = Options { program: vec![1105, 1, 0], detect_loops: true, ..Options::default() };
+        assert_eq!(run(&options, &mut vec![]).unwrap_err(), "stuck in a loop at ip 0");
+    }
+
+    #[test]
+    fn test_detect_loops_with_terminal() {
+        // echoes every value it reads, forever; reading the same value twice is not a loop
+        let mut vm = Vm::new(vec![3, 100, 4, 100, 1105, 1, 0]);
+        let mut outputs = vec![];
+        let mut session = Session {
+            output: &mut outputs,
+            terminal: Some(Box::new(VecDeque::from([5, 5, 7]))),
+            tracer: None,
+            profiler: None,
+            steps: 0,
+        };
+        let limits = Limits { detect_loops: true, ..Limits::default() };
+        assert_eq!(vm.run_observed(limits, &mut session).unwrap(), Status::NeedsInput);
+        assert_eq!(outputs, vec![5, 5, 7]);
+    }
+
+    #[test]
+    fn test_run_waiting_for_input() {
+        // reads two inputs, echoing each; only one is given
+        let options = Options { program: vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0], inputs: vec![7], ..Options::default() };
+        let mut outputs = vec![];
+        let summary = run(&options, &mut outputs).unwrap();
+        assert_eq!(outputs, vec![7]);
+        assert_eq!(summary.status, Status::NeedsInput);
+    }
+
+    #[test]
+    fn test_day2_alarm() {
+        let options = parse_args(&args(&["./inputs/day2", "--patch", "./inputs/patches:1202-alarm", "--print", "0", "--profile"])).unwrap();
+        let by_hand = parse_args(&args(&["./inputs/day2", "--set", "1=12", "--set", "2=2"])).unwrap();
+        assert_eq!(options.patches, by_hand.patches);
+        let summary = run(&options, &mut vec![]).unwrap();
+        assert_eq!(summary.status, Status::Halted);
+
+        // the original day2 machine, patched the same way
+        let program: Vec<u32> = options.program.iter().map(|value| *value as u32).collect();
+        let expected = day2::patched_computer(program, &patch::alarm_1202()).unwrap()[0];
+        assert_eq!(summary.cells, vec![(0, expected as i64)]);
+        assert!(summary.profile.unwrap().contains("Multiply"));
+
+        let err = parse_args(&args(&["./inputs/day2", "--set", "0=1", "--patch", "./inputs/patches:missing"])).unwrap_err();
+        assert!(err.contains("no patch set named \"missing\""), "{err}");
+    }
+
+    #[test]
+    fn test_ascii_and_trace() {
+        // echoes the line it reads
+        let program = vec![3, 100, 4, 100, 1008, 100, 10, 101, 1006, 101, 0, 99];
+        let trace = temp_path("trace");
+        let options = parse_args(&args(&["./inputs/day2", "--line", "ok"])).unwrap();
+        let options = Options { program, trace: Some(trace.clone()), ascii: true, ..options };
+        let mut output = AsciiOutput::new();
+        let summary = run(&options, &mut output).unwrap();
+        let traced = fs::read_to_string(&trace).unwrap();
+        fs::remove_file(&trace).unwrap();
+        assert_eq!(output.lines, vec!["ok"]);
+        assert_eq!(summary.steps, 3 * 4 + 1);
+        assert_eq!(traced.lines().count(), 13);
+    }
+}
@@ -0,0 +1,424 @@
+use std::time::{Duration, Instant};
+
+use crate::intcode::vm::{Cell, Status, Vm};
+use crate::intcode::{load_program, parse_inputs, Instruction, Opcode, ParamMode};
+
+const BENCH_RUNS: u32 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Immediate(i64),
+    Position(usize),
+    Relative(i64),
+}
+
+// An instruction decoded once, with its parameter modes folded into the operands. An
+// immediate mode destination becomes a position, since that is the cell it writes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Sum(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, Operand),
+    JumpIfFalse(Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    RelativeBaseOffset(Operand),
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Slot {
+    Empty,
+    Compiled(Op, usize),
+    // code here was written after it was compiled, or never decoded cleanly
+    Interpreted,
+}
+
+fn compile(memory: &[i64], address: usize) -> Option<(Op, usize)> {
+    let instruction = Instruction::decode(*memory.get(address)?).ok()?;
+    let size = instruction.size();
+    if address + size > memory.len() {
+        return None;
+    }
+    let operand = |index: usize| {
+        let cell = address + 1 + index;
+        match instruction.modes[index] {
+            ParamMode::Positional => usize::try_from(memory[cell]).ok().map(Operand::Position),
+            ParamMode::Immediate if instruction.opcode.write_param() == Some(index) => Some(Operand::Position(cell)),
+            ParamMode::Immediate => Some(Operand::Immediate(memory[cell])),
+            ParamMode::Relative => Some(Operand::Relative(memory[cell])),
+        }
+    };
+    let op = match instruction.opcode {
+        Opcode::Sum => Op::Sum(operand(0)?, operand(1)?, operand(2)?),
+        Opcode::Multiply => Op::Multiply(operand(0)?, operand(1)?, operand(2)?),
+        Opcode::Input => Op::Input(operand(0)?),
+        Opcode::Output => Op::Output(operand(0)?),
+        Opcode::JumpIfTrue => Op::JumpIfTrue(operand(0)?, operand(1)?),
+        Opcode::JumpIfFalse => Op::JumpIfFalse(operand(0)?, operand(1)?),
+        Opcode::LessThan => Op::LessThan(operand(0)?, operand(1)?, operand(2)?),
+        Opcode::Equals => Op::Equals(operand(0)?, operand(1)?, operand(2)?),
+        Opcode::RelativeBaseOffset => Op::RelativeBaseOffset(operand(0)?),
+        Opcode::Stop => Op::Stop,
+    };
+    Some((op, size))
+}
+
+// Runs a `Vm` from a cache of decoded instructions instead of decoding each one as it
+// executes. Anything out of the ordinary, like an instruction that would fail, or code
+// that has been written to since it was compiled, is handed to `Vm::step` so results and
+// errors are exactly those of the interpreter.
+pub struct CompiledVm {
+    pub vm: Vm,
+    slots: Vec<Slot>,
+    // cells that are part of a compiled instruction
+    code: Vec<bool>,
+    pub interpreted: u64,
+}
+
+impl CompiledVm {
+    pub fn new(vm: Vm) -> Self {
+        let len = vm.memory.len();
+        Self { vm, slots: vec![Slot::Empty; len], code: vec![false; len], interpreted: 0 }
+    }
+
+    // compiles every instruction the program starts out with
+    pub fn warm(mut self) -> Self {
+        for address in 0..self.slots.len() {
+            self.slot(address);
+        }
+        self
+    }
+
+    // Swaps in `vm` and a copy of `warm`'s cache, so a program run over and over, like day2
+    // with each noun and verb, is only compiled once even though it writes over its own
+    // code. Instructions over cells where the memories of `vm` and `warm` differ are dropped.
+    pub fn reset(&mut self, warm: &CompiledVm, vm: Vm) {
+        self.slots.clone_from(&warm.slots);
+        self.code.clone_from(&warm.code);
+        self.vm = vm;
+        self.interpreted = 0;
+        for address in 0..self.slots.len() {
+            if self.vm.memory.get(address) != warm.vm.memory.get(address) {
+                self.invalidate(address);
+            }
+        }
+        self.slots.resize(self.vm.memory.len(), Slot::Empty);
+        self.code.resize(self.vm.memory.len(), false);
+    }
+
+    fn slot(&mut self, address: usize) -> Slot {
+        if address >= self.slots.len() {
+            return Slot::Interpreted;
+        }
+        if self.slots[address] == Slot::Empty {
+            self.slots[address] = match compile(&self.vm.memory, address) {
+                Some((op, size)) => {
+                    self.code[address..address + size].fill(true);
+                    Slot::Compiled(op, size)
+                }
+                None => Slot::Interpreted,
+            };
+        }
+        self.slots[address]
+    }
+
+    // a write landed on `address`, so whatever was compiled over it is stale
+    fn invalidate(&mut self, address: usize) {
+        if !self.code.get(address).copied().unwrap_or(false) {
+            return;
+        }
+        for start in address.saturating_sub(3)..=address {
+            if let Slot::Compiled(_, size) = self.slots[start] {
+                if start + size > address {
+                    self.slots[start] = Slot::Interpreted;
+                }
+            }
+        }
+    }
+
+    fn address(&self, operand: Operand) -> Option<usize> {
+        match operand {
+            Operand::Position(address) => Some(address),
+            Operand::Relative(offset) => self.vm.relative_base.checked_add(offset)?.to_address(),
+            Operand::Immediate(_) => None,
+        }
+    }
+
+    fn load(&self, operand: Operand) -> Option<i64> {
+        match operand {
+            Operand::Immediate(value) => Some(value),
+            _ => Some(self.vm.read(self.address(operand)?)),
+        }
+    }
+
+    fn store(&mut self, address: usize, value: i64) -> bool {
+        if self.vm.write(address, value).is_err() {
+            return false;
+        }
+        self.invalidate(address);
+        true
+    }
+
+    // Executes `op` if nothing can go wrong, leaving the state untouched and returning
+    // false otherwise.
+    fn execute(&mut self, op: Op, size: usize) -> bool {
+        let ip = self.vm.instruction_pointer;
+        let mut next_ip = ip + size;
+        match op {
+            Op::Sum(a, b, dest) | Op::Multiply(a, b, dest) | Op::LessThan(a, b, dest) | Op::Equals(a, b, dest) => {
+                let (Some(a), Some(b), Some(dest)) = (self.load(a), self.load(b), self.address(dest)) else { return false };
+                let value = match op {
+                    Op::Sum(..) => a.add_with(b, self.vm.overflow),
+                    Op::Multiply(..) => a.mul_with(b, self.vm.overflow),
+                    Op::LessThan(..) => Some((a < b) as i64),
+                    _ => Some((a == b) as i64),
+                };
+                let Some(value) = value else { return false };
+                if !self.store(dest, value) {
+                    return false;
+                }
+            }
+            Op::Input(dest) => {
+                let (Some(dest), Some(&value)) = (self.address(dest), self.vm.inputs.front()) else { return false };
+                if !self.store(dest, value) {
+                    return false;
+                }
+                self.vm.inputs.pop_front();
+            }
+            Op::Output(a) => {
+                let Some(value) = self.load(a) else { return false };
+                self.vm.outputs.push(value);
+            }
+            Op::JumpIfTrue(a, target) | Op::JumpIfFalse(a, target) => {
+                let (Some(a), Some(target)) = (self.load(a), self.load(target)) else { return false };
+                if (a != 0) == matches!(op, Op::JumpIfTrue(..)) {
+                    let Some(target) = target.to_address() else { return false };
+                    next_ip = target;
+                }
+            }
+            Op::RelativeBaseOffset(a) => {
+                let Some(relative_base) = self.load(a).and_then(|offset| self.vm.relative_base.add_with(offset, self.vm.overflow)) else { return false };
+                self.vm.relative_base = relative_base;
+            }
+            Op::Stop => {
+                self.vm.halted = true;
+                next_ip = ip;
+            }
+        }
+        self.vm.instruction_pointer = next_ip;
+        true
+    }
+
+    // same contract as `Vm::run`
+    pub fn run(&mut self) -> Result<Status, String> {
+        self.run_with_limit(None)
+    }
+
+    // `run` that gives up with the same error as `Vm::run_with_limits` once `max_steps`
+    // instructions have executed
+    pub fn run_with_limit(&mut self, max_steps: Option<u64>) -> Result<Status, String> {
+        let mut steps = 0;
+        while !self.vm.halted {
+            if max_steps == Some(steps) && self.vm.status() == Status::Running {
+                return Err(format!("step limit of {} reached at ip {}", steps, self.vm.instruction_pointer));
+            }
+            // memory grew past what has been compiled
+            if self.slots.len() < self.vm.memory.len() {
+                self.slots.resize(self.vm.memory.len(), Slot::Empty);
+                self.code.resize(self.vm.memory.len(), false);
+            }
+            if let Slot::Compiled(op, size) = self.slot(self.vm.instruction_pointer) {
+                if self.execute(op, size) {
+                    steps += 1;
+                    continue;
+                }
+            }
+            match self.vm.step()? {
+                Some(step) => {
+                    steps += 1;
+                    self.interpreted += 1;
+                    if let Some(write) = step.write {
+                        self.invalidate(write.address);
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(self.vm.status())
+    }
+}
+
+fn time<T>(runs: u32, mut f: impl FnMut() -> Result<T, String>) -> Result<(Duration, T), String> {
+    let start = Instant::now();
+    let mut result = f()?;
+    for _ in 1..runs {
+        result = f()?;
+    }
+    Ok((start.elapsed(), result))
+}
+
+fn compare(name: &str, runs: u32, interpreter: Duration, compiled: Duration) -> String {
+    format!("{name}, {runs} runs\n  interpreter {:>10.2?}\n  compiled    {:>10.2?}\n  speedup     {:>9.2}x",
+            interpreter, compiled, interpreter.as_secs_f64() / compiled.as_secs_f64().max(f64::EPSILON))
+}
+
+// times both backends on the same program and inputs, failing if they disagree
+pub fn benchmark(program: &[i64], inputs: &[i64], runs: u32) -> Result<String, String> {
+    let (interpreter, expected) = time(runs, || {
+        let mut vm = Vm::with_inputs(program.to_vec(), inputs);
+        vm.run().map(|status| (status, vm))
+    })?;
+    let (compiled, actual) = time(runs, || {
+        let mut compiled = CompiledVm::new(Vm::with_inputs(program.to_vec(), inputs));
+        compiled.run().map(|status| (status, compiled.vm))
+    })?;
+    if actual != expected {
+        return Err("the compiled backend disagrees with the interpreter".to_string());
+    }
+    Ok(compare("program", runs, interpreter, compiled))
+}
+
+// the day2 brute force: every noun and verb from 0 to 99 patched into a fresh copy, with
+// the program compiled only once for all of them
+pub fn benchmark_day2(program: &[i64]) -> Result<String, String> {
+    let patched = |noun: i64, verb: i64| {
+        let mut program = program.to_vec();
+        program[1] = noun;
+        program[2] = verb;
+        Vm::new(program)
+    };
+    let pairs = || (0..100).flat_map(|noun| (0..100).map(move |verb| (noun, verb)));
+    let (interpreter, expected) = time(1, || {
+        pairs().map(|(noun, verb)| {
+            let mut vm = patched(noun, verb);
+            vm.run().map(|_| vm.read(0))
+        }).collect::<Result<Vec<_>, _>>()
+    })?;
+    let (compiled, actual) = time(1, || {
+        let warm = CompiledVm::new(Vm::new(program.to_vec())).warm();
+        let mut compiled = CompiledVm::new(Vm::new(vec![]));
+        pairs().map(|(noun, verb)| {
+            compiled.reset(&warm, patched(noun, verb));
+            compiled.run().map(|_| compiled.vm.read(0))
+        }).collect::<Result<Vec<_>, _>>()
+    })?;
+    if actual != expected {
+        return Err("the compiled backend disagrees with the interpreter".to_string());
+    }
+    Ok(compare("day2, 10000 noun/verb pairs", 1, interpreter, compiled))
+}
+
+// `bench <program file> [input ...]` or `bench --day2 <program file>`
+pub fn main(args: &[String]) {
+    let result = match args {
+        [flag, path] if flag == "--day2" => load_program(path).and_then(|program| benchmark_day2(&program)),
+        [path, inputs @ ..] => load_program(path).and_then(|program| {
+            let inputs = parse_inputs(inputs)?;
+            benchmark(&program, &inputs, BENCH_RUNS)
+        }),
+        [] => Err("usage: bench <program file> [input ...] | bench --day2 <program file>".to_string()),
+    };
+    match result {
+        Ok(report) => println!("{report}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::vm::{Limits, Overflow};
+
+    fn assert_same(program: Vec<i64>, inputs: &[i64]) -> CompiledVm {
+        let mut expected = Vm::with_inputs(program.clone(), inputs);
+        let expected_status = expected.run();
+        let mut compiled = CompiledVm::new(Vm::with_inputs(program, inputs));
+        assert_eq!(compiled.run(), expected_status);
+        assert_eq!(compiled.vm, expected);
+        compiled
+    }
+
+    #[test]
+    fn test_puzzle_inputs() {
+        let mut day2 = load_program("./inputs/day2").unwrap();
+        day2[1] = 12;
+        day2[2] = 2;
+        assert_eq!(assert_same(day2, &[]).interpreted, 0);
+        assert_same(load_program("./inputs/day5").unwrap(), &[5]);
+        assert_same(load_program("./inputs/day9").unwrap(), &[2]);
+        assert_same(load_program("./inputs/day13").unwrap(), &[]);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // adds 5 on the first pass, then patches the Sum that already ran into a Multiply
+        let program = vec![1001, 20, 5, 20, 1101, 1002, 0, 0, 1001, 21, -1, 21, 1005, 21, 0, 4, 20, 99, 0, 0, 1, 2];
+        let compiled = assert_same(program, &[]);
+        assert_eq!(compiled.vm.outputs, vec![30]);
+        assert!(compiled.interpreted > 0);
+
+        // writes into its own parameter through an immediate destination
+        let compiled = assert_same(vec![11101, 2, 2, 3, 4, 3, 99], &[]);
+        assert_eq!(compiled.vm.outputs, vec![4]);
+    }
+
+    #[test]
+    fn test_input_and_errors() {
+        // outputs the sum of each pair of inputs
+        let program = vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 1105, 1, 0, 0, 0, 0];
+        let mut compiled = CompiledVm::new(Vm::with_inputs(program.clone(), &[1, 2, 3]));
+        assert_eq!(compiled.run().unwrap(), Status::NeedsInput);
+        compiled.vm.push_input(4);
+        assert_eq!(compiled.run().unwrap(), Status::NeedsInput);
+        assert_eq!(compiled.vm.outputs, vec![3, 7]);
+        assert_same(program, &[1, 2, 3]);
+
+        assert_same(vec![4, -3, 99], &[]);
+        assert_same(vec![109, -5, 204, 0, 99], &[]);
+        assert_same(vec![42], &[]);
+        let overflow = vec![1102, i64::MAX, 2, 5, 99, 0];
+        assert_same(overflow.clone(), &[]);
+        let mut compiled = CompiledVm::new(Vm::new(overflow).with_overflow(Overflow::Saturate));
+        compiled.run().unwrap();
+        assert_eq!(compiled.vm.read(5), i64::MAX);
+    }
+
+    #[test]
+    fn test_reset() {
+        let program = load_program("./inputs/day2").unwrap();
+        let warm = CompiledVm::new(Vm::new(program.clone())).warm();
+        let mut compiled = CompiledVm::new(Vm::new(vec![]));
+        for (noun, verb) in [(12, 2), (0, 0), (99, 99), (12, 2)] {
+            let mut patched = program.clone();
+            patched[1] = noun;
+            patched[2] = verb;
+            let mut expected = Vm::new(patched.clone());
+            expected.run().unwrap();
+            compiled.reset(&warm, Vm::new(patched));
+            assert_eq!(compiled.run().unwrap(), Status::Halted);
+            assert_eq!(compiled.vm, expected);
+            // only the first instruction reads the patched cells, and day2 itself has both at 0
+            assert_eq!(compiled.interpreted, ((noun, verb) != (0, 0)) as u64);
+        }
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let program = vec![1105, 1, 0];
+        let mut expected = Vm::new(program.clone());
+        let mut compiled = CompiledVm::new(Vm::new(program));
+        assert_eq!(compiled.run_with_limit(Some(50)), expected.run_with_limits(Limits { max_steps: Some(50), detect_loops: false }));
+        assert_eq!(compiled.run_with_limit(Some(50)).unwrap_err(), "step limit of 50 reached at ip 0");
+        let mut compiled = CompiledVm::new(Vm::new(vec![1101, 1, 1, 5, 99, 0]));
+        assert_eq!(compiled.run_with_limit(Some(2)).unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn test_benchmark() {
+        let program = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+        assert!(benchmark(&program, &[], 2).unwrap().contains("speedup"));
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, BufRead, Write as _};
+
+use crate::intcode::analysis::analyse;
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::dump::{diff, dump, load_vm};
+use crate::intcode::history::History;
+use crate::intcode::snapshot;
+use crate::intcode::vm::Vm;
+use crate::intcode::{load_program, Opcode};
+
+const HELP: &str = "\
+step [n]              execute n instructions (default 1)
+continue              run until a breakpoint, watchpoint, input wait or halt
+back [n]              undo n instructions (default 1)
+rcontinue             run backwards until a breakpoint, watchpoint or the start
+goto n                go back to how things were after n instructions
+writes addr           list the executed instructions that wrote to addr
+history [every keep]  show the undo history, or snapshot every n steps and keep the last m (clears it)
+break [addr|opcode]   add a breakpoint, or list them without an argument
+delete addr|opcode    remove a breakpoint
+watch [addr]          stop after a write to addr, or list watchpoints
+unwatch addr          remove a watchpoint
+input v1 v2 ...       queue input values
+mem addr [count]      show memory cells
+dump [addr] [count]   show memory as a table with decoded instructions (default: 0, 50 cells)
+mark                  remember the current state for `diff`
+diff [file]           show what changed since `mark`, or since a saved snapshot
+set addr value        write a memory cell (clears the history)
+rb [value]            show or set the relative base (setting clears the history)
+ip [value]            show or set the instruction pointer (setting clears the history)
+dis [addr] [count]    disassemble (default: at ip, 10 instructions)
+analyse               separate code from data in memory and list suspicious constructs
+out                   show outputs produced so far
+info                  show ip, relative base, queued input and status
+save file             write a snapshot of the VM state
+load file             restore the VM state from a snapshot (clears the history)
+quit";
+
+pub struct Debugger {
+    pub vm: Vm,
+    breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: HashSet<Opcode>,
+    watchpoints: BTreeSet<usize>,
+    history: History,
+    mark: Option<Vm>,
+}
+
+fn parse_number<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing {what}"))?;
+    arg.parse::<T>().map_err(|_| format!("invalid {what} {arg:?}"))
+}
+
+impl Debugger {
+    pub fn new(vm: Vm) -> Self {
+        Self { vm, breakpoints: BTreeSet::new(), opcode_breakpoints: HashSet::new(), watchpoints: BTreeSet::new(), history: History::default(), mark: None }
+    }
+
+    fn location(&self) -> String {
+        let ip = self.vm.instruction_pointer;
+        match disassemble_instruction(&self.vm.memory, ip) {
+            Some((text, _)) => format!("{ip:>6}: {text}"),
+            None => format!("{ip:>6}: .data {}", self.vm.read(ip)),
+        }
+    }
+
+    fn at_breakpoint(&self) -> Option<String> {
+        let ip = self.vm.instruction_pointer;
+        if self.breakpoints.contains(&ip) {
+            return Some(format!("breakpoint at {ip}"));
+        }
+        match self.vm.current_instruction() {
+            Ok(instruction) if self.opcode_breakpoints.contains(&instruction.opcode) => {
+                Some(format!("breakpoint on {:?}", instruction.opcode))
+            }
+            _ => None,
+        }
+    }
+
+    // Executes up to `limit` instructions, stopping early on breakpoints (except at the
+    // instruction we resume from), watched writes, input waits and halts.
+    fn resume(&mut self, limit: Option<usize>) -> Result<String, String> {
+        let outputs_before = self.vm.outputs.len();
+        let mut executed = 0;
+        let reason = loop {
+            if limit == Some(executed) {
+                break None;
+            }
+            if executed > 0 {
+                if let Some(reason) = self.at_breakpoint() {
+                    break Some(reason);
+                }
+            }
+            match self.vm.step()? {
+                Some(step) => {
+                    executed += 1;
+                    self.history.record(&self.vm, step);
+                    if let Some(write) = step.write.filter(|write| self.watchpoints.contains(&write.address)) {
+                        break Some(format!("watchpoint {}: {} -> {} at ip {}", write.address, write.old, write.new, step.instruction_pointer));
+                    }
+                }
+                None if self.vm.halted => break Some("halted".to_string()),
+                None => break Some("waiting for input".to_string()),
+            }
+        };
+
+        let mut report = vec![];
+        let new_outputs = &self.vm.outputs[outputs_before..];
+        match new_outputs.len() {
+            0 => {}
+            1..=10 => report.push(format!("output: {new_outputs:?}")),
+            count => report.push(format!("{count} outputs, see `out`")),
+        }
+        if let Some(reason) = reason {
+            report.push(reason);
+        }
+        report.push(self.location());
+        Ok(report.join("\n"))
+    }
+
+    // Undoes up to `limit` instructions. Stops when undoing a write to a watched cell, leaving
+    // the VM just before that write, or on reaching a breakpoint.
+    fn reverse(&mut self, limit: Option<usize>) -> String {
+        let mut undone = 0;
+        let reason = loop {
+            if limit == Some(undone) {
+                break None;
+            }
+            let Some(step) = self.history.undo(&mut self.vm) else { break Some("at the start of the history".to_string()) };
+            undone += 1;
+            if let Some(write) = step.write.filter(|write| self.watchpoints.contains(&write.address)) {
+                break Some(format!("watchpoint {}: {} -> {} at ip {} undone", write.address, write.new, write.old, step.instruction_pointer));
+            }
+            if limit.is_none() {
+                if let Some(reason) = self.at_breakpoint() {
+                    break Some(reason);
+                }
+            }
+        };
+        let mut report = vec![format!("back {undone} to step {}", self.history.position())];
+        report.extend(reason);
+        report.push(self.location());
+        report.join("\n")
+    }
+
+    fn writes(&self, address: usize) -> String {
+        let lines: Vec<String> = self.history.steps()
+            .filter_map(|(number, step)| step.write.filter(|write| write.address == address).map(|write| (number, step, write)))
+            .map(|(number, step, write)| {
+                let text = disassemble_instruction(&self.vm.memory, step.instruction_pointer).map(|(text, _)| text).unwrap_or_default();
+                format!("step {:>8} {:>6}: {:<32} {} -> {}", number + 1, step.instruction_pointer, text, write.old, write.new)
+            })
+            .collect();
+        if lines.is_empty() { format!("no writes to {address} in the history") } else { lines.join("\n") }
+    }
+
+    fn toggle_breakpoint(&mut self, arg: Option<&str>, add: bool) -> Result<String, String> {
+        let Some(arg) = arg else {
+            let mut list: Vec<String> = self.breakpoints.iter().map(|address| address.to_string()).collect();
+            list.extend(self.opcode_breakpoints.iter().map(|opcode| format!("{opcode:?}")));
+            return Ok(format!("breakpoints: {}", list.join(", ")));
+        };
+        let changed = match Opcode::from_name(arg) {
+            Some(opcode) if add => self.opcode_breakpoints.insert(opcode),
+            Some(opcode) => self.opcode_breakpoints.remove(&opcode),
+            None => {
+                let address = parse_number(Some(arg), "address")?;
+                if add { self.breakpoints.insert(address) } else { self.breakpoints.remove(&address) }
+            }
+        };
+        match (changed, add) {
+            (true, true) => Ok(format!("breakpoint set on {arg}")),
+            (true, false) => Ok(format!("breakpoint on {arg} removed")),
+            (false, _) => Err(format!("no change for breakpoint {arg}")),
+        }
+    }
+
+    fn memory(&self, args: &[&str]) -> Result<String, String> {
+        let start: usize = parse_number(args.first().copied(), "address")?;
+        let count: usize = if args.len() > 1 { parse_number(args.get(1).copied(), "count")? } else { 1 };
+        let end = start.checked_add(count).ok_or_else(|| format!("{count} cells from {start} run past the last address"))?;
+        let lines: Vec<String> = (start..end).map(|address| format!("{address:>6}: {}", self.vm.read(address))).collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn disassemble(&self, args: &[&str]) -> Result<String, String> {
+        let mut address: usize = if args.is_empty() { self.vm.instruction_pointer } else { parse_number(args.first().copied(), "address")? };
+        let count: usize = if args.len() > 1 { parse_number(args.get(1).copied(), "count")? } else { 10 };
+        let mut lines = vec![];
+        for _ in 0..count {
+            if address >= self.vm.memory.len() {
+                break;
+            }
+            let marker = if address == self.vm.instruction_pointer { "=>" } else { "  " };
+            let (text, size) = disassemble_instruction(&self.vm.memory, address)
+                .unwrap_or_else(|| (format!(".data {}", self.vm.memory[address]), 1));
+            lines.push(format!("{marker}{address:>6}: {text}"));
+            address += size;
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn info(&self) -> String {
+        format!("ip: {}\nrelative base: {}\nqueued input: {:?}\noutputs: {}\nstatus: {:?}\nstep: {}\n{}",
+                self.vm.instruction_pointer, self.vm.relative_base, self.vm.inputs, self.vm.outputs.len(), self.vm.status(),
+                self.history.position(), self.location())
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = words.split_first() else { return Ok(String::new()) };
+        let arg = args.first().copied();
+
+        match *command {
+            "s" | "step" => {
+                let count = if arg.is_some() { parse_number(arg, "count")? } else { 1 };
+                self.resume(Some(count))
+            }
+            "c" | "continue" => self.resume(None),
+            "back" => {
+                let count = if arg.is_some() { parse_number(arg, "count")? } else { 1 };
+                Ok(self.reverse(Some(count)))
+            }
+            "rc" | "rcontinue" => Ok(self.reverse(None)),
+            "goto" => {
+                self.history.rewind(&mut self.vm, parse_number(arg, "step")?)?;
+                Ok(format!("at step {}\n{}", self.history.position(), self.location()))
+            }
+            "writes" => Ok(self.writes(parse_number(arg, "address")?)),
+            "history" => {
+                if arg.is_some() {
+                    self.history = History::new(parse_number(arg, "interval")?, parse_number(args.get(1).copied(), "step count")?);
+                }
+                Ok(format!("steps {}..{} kept, snapshot every {}, keeping the last {}",
+                           self.history.start(), self.history.position(), self.history.interval(), self.history.capacity()))
+            }
+            "b" | "break" => self.toggle_breakpoint(arg, true),
+            "d" | "delete" => self.toggle_breakpoint(Some(arg.ok_or("missing breakpoint")?), false),
+            "w" | "watch" => match arg {
+                Some(_) => {
+                    let address = parse_number(arg, "address")?;
+                    self.watchpoints.insert(address);
+                    Ok(format!("watching {address}"))
+                }
+                None => Ok(format!("watchpoints: {:?}", self.watchpoints)),
+            },
+            "unwatch" => {
+                let address = parse_number(arg, "address")?;
+                if self.watchpoints.remove(&address) { Ok(format!("stopped watching {address}")) } else { Err(format!("{address} is not watched")) }
+            }
+            "i" | "input" => {
+                let values = args.iter().map(|value| parse_number(Some(value), "input")).collect::<Result<Vec<i64>, _>>()?;
+                self.vm.inputs.extend(values);
+                Ok(format!("queued input: {:?}", self.vm.inputs))
+            }
+            "x" | "mem" => self.memory(args),
+            "dump" => {
+                let start: usize = if arg.is_some() { parse_number(arg, "address")? } else { 0 };
+                let count: usize = if args.len() > 1 { parse_number(args.get(1).copied(), "count")? } else { 50 };
+                Ok(dump(&self.vm.memory, start..start.saturating_add(count)))
+            }
+            "mark" => {
+                self.mark = Some(self.vm.clone());
+                Ok(format!("marked at step {}", self.history.position()))
+            }
+            "diff" => {
+                let before = match arg {
+                    Some(path) => load_vm(path)?,
+                    None => self.mark.clone().ok_or("nothing marked, use `mark` first")?,
+                };
+                Ok(diff(&before, &self.vm))
+            }
+            "set" => {
+                let address = parse_number(arg, "address")?;
+                let value = parse_number(args.get(1).copied(), "value")?;
+                let write = self.vm.write(address, value)?;
+                self.history.clear();
+                Ok(format!("{}: {} -> {}", write.address, write.old, write.new))
+            }
+            "rb" => {
+                if arg.is_some() {
+                    self.vm.relative_base = parse_number(arg, "relative base")?;
+                    self.history.clear();
+                }
+                Ok(format!("relative base: {}", self.vm.relative_base))
+            }
+            "ip" => {
+                if arg.is_some() {
+                    self.vm.instruction_pointer = parse_number(arg, "address")?;
+                    self.vm.halted = false;
+                    self.history.clear();
+                }
+                Ok(self.location())
+            }
+            "dis" => self.disassemble(args),
+            "analyse" => Ok(analyse(&self.vm.memory).report()),
+            "out" => Ok(format!("{:?}", self.vm.outputs)),
+            "info" => Ok(self.info()),
+            "save" => {
+                let path = arg.ok_or("missing file")?;
+                snapshot::save(&self.vm, path)?;
+                Ok(format!("saved to {path}"))
+            }
+            "load" => {
+                self.vm = snapshot::load(arg.ok_or("missing file")?)?;
+                self.history.clear();
+                Ok(self.location())
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command {command:?}, try `help`")),
+        }
+    }
+
+    pub fn run_repl(&mut self) {
+        let stdin = io::stdin();
+        let mut last_command = String::from("step");
+        println!("{}", self.location());
+        loop {
+            print!("(intcode) ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            // an empty line repeats the previous command
+            let line = if line.trim().is_empty() { last_command.clone() } else { line.trim().to_string() };
+            if line == "q" || line == "quit" {
+                break;
+            }
+            match self.execute(&line) {
+                Ok(text) => println!("{text}"),
+                Err(e) => println!("error: {e}"),
+            }
+            last_command = line;
+        }
+    }
+}
+
+// `debug <program file> [input ...]`
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: debug <program file> [input ...]");
+        return;
+    };
+    let program = match load_program(path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    // a corrupted program file usually shows up here before it misbehaves
+    for warning in analyse(&program).warnings {
+        eprintln!("warning: {warning}");
+    }
+    let mut debugger = Debugger::new(Vm::new(program));
+    if args.len() > 1 {
+        if let Err(e) = debugger.execute(&format!("input {}", args[1..].join(" "))) {
+            eprintln!("{e}");
+            return;
+        }
+    }
+    debugger.run_repl();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_debugger() -> Debugger {
+        // counts memory[20] down from 3, outputting it each round
+        Debugger::new(Vm::new(vec![4, 20, 1001, 20, -1, 20, 1005, 20, 0, 99]))
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = create_debugger();
+        debugger.vm.write(20, 3).unwrap();
+        debugger.execute("break 6").unwrap();
+        assert!(debugger.execute("continue").unwrap().contains("breakpoint at 6"));
+        assert_eq!(debugger.vm.outputs, vec![3]);
+        debugger.execute("delete 6").unwrap();
+        debugger.execute("break Output").unwrap();
+        assert!(debugger.execute("c").unwrap().contains("breakpoint on Output"));
+        debugger.execute("d output").unwrap();
+        assert!(debugger.execute("c").unwrap().contains("halted"));
+        assert_eq!(debugger.vm.outputs, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_watchpoint_and_edit() {
+        let mut debugger = create_debugger();
+        debugger.execute("set 20 2").unwrap();
+        debugger.execute("watch 20").unwrap();
+        let report = debugger.execute("c").unwrap();
+        assert!(report.contains("watchpoint 20: 2 -> 1 at ip 2"), "{report}");
+        assert_eq!(debugger.execute("mem 20").unwrap().trim(), "20: 1");
+        assert_eq!(debugger.execute("mem 18446744073709551615 2").unwrap_err(), "2 cells from 18446744073709551615 run past the last address");
+        assert_eq!(debugger.execute("dump 18446744073709551615 2").unwrap().lines().count(), 1);
+        debugger.execute("rb 7").unwrap();
+        assert_eq!(debugger.vm.relative_base, 7);
+    }
+
+    #[test]
+    fn test_input_queue() {
+        let mut debugger = Debugger::new(Vm::new(vec![3, 0, 4, 0, 99]));
+        assert!(debugger.execute("c").unwrap().contains("waiting for input"));
+        debugger.execute("input 42").unwrap();
+        let report = debugger.execute("c").unwrap();
+        assert!(report.contains("output: [42]"));
+        assert!(report.contains("halted"));
+        assert!(debugger.execute("bogus").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("intcode_debugger_snapshot_test.txt");
+        let path = path.to_str().unwrap();
+        let mut debugger = create_debugger();
+        debugger.execute("set 20 2").unwrap();
+        debugger.execute("step").unwrap();
+        debugger.execute(&format!("save {path}")).unwrap();
+        debugger.execute("c").unwrap();
+        assert!(debugger.vm.halted);
+        debugger.execute(&format!("load {path}")).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(debugger.vm.instruction_pointer, 2);
+        assert_eq!(debugger.vm.outputs, vec![2]);
+        assert_eq!(debugger.vm.read(20), 2);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut debugger = create_debugger();
+        debugger.execute("set 20 3").unwrap();
+        assert!(debugger.execute("c").unwrap().contains("halted"));
+        assert_eq!(debugger.history.position(), 10);
+
+        let report = debugger.execute("back 2").unwrap();
+        assert!(report.starts_with("back 2 to step 8"), "{report}");
+        assert_eq!(debugger.vm.instruction_pointer, 6);
+        assert_eq!(debugger.vm.outputs, vec![3, 2, 1]);
+
+        // find the write that took the counter to 2
+        debugger.execute("watch 20").unwrap();
+        assert!(debugger.execute("rc").unwrap().contains("watchpoint 20: 0 -> 1 at ip 2 undone"));
+        debugger.execute("rc").unwrap();
+        let report = debugger.execute("rc").unwrap();
+        assert!(report.contains("watchpoint 20: 2 -> 3 at ip 2 undone"), "{report}");
+        assert_eq!(debugger.vm.read(20), 3);
+        assert_eq!(debugger.vm.outputs, vec![3]);
+        assert!(debugger.execute("writes 20").unwrap().starts_with("no writes"));
+
+        debugger.execute("unwatch 20").unwrap();
+        debugger.execute("c").unwrap();
+        let writes = debugger.execute("writes 20").unwrap();
+        assert_eq!(writes.lines().count(), 3);
+        let first = writes.lines().next().unwrap();
+        assert!(first.starts_with("step        2      2: Sum [20], -1, [20]") && first.ends_with("3 -> 2"), "{first}");
+
+        debugger.execute("goto 1").unwrap();
+        assert_eq!(debugger.vm.outputs, vec![3]);
+        assert!(debugger.execute("rc").unwrap().contains("at the start of the history"));
+        assert!(debugger.execute("goto 5").is_err());
+
+        // with only the last 4 steps kept, reversing stops 4 steps back
+        debugger.execute("history 2 4").unwrap();
+        debugger.execute("set 20 3").unwrap();
+        debugger.execute("c").unwrap();
+        assert_eq!(debugger.execute("history").unwrap(), "steps 6..10 kept, snapshot every 2, keeping the last 4");
+        assert!(debugger.execute("rc").unwrap().starts_with("back 4 to step 6"));
+        assert!(debugger.execute("history 2").is_err());
+    }
+
+    #[test]
+    fn test_mark_and_diff() {
+        let mut debugger = create_debugger();
+        assert!(debugger.execute("diff").is_err());
+        debugger.execute("set 20 3").unwrap();
+        debugger.execute("mark").unwrap();
+        debugger.execute("step 4").unwrap();
+        let report = debugger.execute("diff").unwrap();
+        assert!(report.starts_with("ip 0 -> 2\n2 new outputs\n1 cells changed"), "{report}");
+        assert!(report.ends_with("20               3 -> 2                    -1"), "{report}");
+        assert!(debugger.execute("dump 0 3").unwrap().ends_with("     2            1001  Sum [20], -1, [20]"));
+    }
+
+    #[test]
+    fn test_step_count() {
+        let mut debugger = create_debugger();
+        debugger.execute("step 2").unwrap();
+        assert_eq!(debugger.vm.instruction_pointer, 6);
+        assert!(debugger.execute("dis").unwrap().starts_with("=>     6: JumpIfTrue [20], 0"));
+    }
+}
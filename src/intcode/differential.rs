@@ -0,0 +1,235 @@
+// Runs the same programs through every Intcode implementation in the tree and checks that
+// they agree with `vm::Vm` on outputs and final memory. The old implementations only get
+// programs they can run: the opcodes and parameter modes they know, values that fit their
+// cell type and addresses inside the memory they allocate.
+
+use std::collections::HashSet;
+use std::sync::mpsc::sync_channel;
+
+use crate::day13::Computer;
+use crate::day2::computer;
+use crate::day5::computer_ver2_with_io;
+use crate::day7::computer_ver3_with_memory;
+use crate::day9::computer_ver5_with_memory;
+use crate::intcode::compiled::CompiledVm;
+use crate::intcode::vm::{Status, Vm};
+use crate::intcode::{load_program, Opcode, ParamMode};
+
+// both day9 and day13 extend the program with this many zeroed cells
+const EXTRA_MEMORY: usize = 16000;
+
+// what a run of the reference VM needed from an implementation
+struct Usage {
+    opcodes: HashSet<Opcode>,
+    modes: HashSet<ParamMode>,
+    // one past the highest address touched, including the instructions themselves
+    memory_used: usize,
+    smallest: i64,
+    largest: i64,
+}
+
+struct Outcome {
+    outputs: Vec<i64>,
+    memory: Vec<i64>,
+}
+
+struct Implementation {
+    name: &'static str,
+    supports: fn(&Usage, &[i64]) -> bool,
+    run: fn(&[i64], &[i64], &Outcome) -> Outcome,
+}
+
+const PART_ONE: [Opcode; 3] = [Opcode::Sum, Opcode::Multiply, Opcode::Stop];
+const PART_TWO: [Opcode; 9] = [Opcode::Sum, Opcode::Multiply, Opcode::Input, Opcode::Output, Opcode::JumpIfTrue,
+                               Opcode::JumpIfFalse, Opcode::LessThan, Opcode::Equals, Opcode::Stop];
+
+impl Usage {
+    fn only(&self, opcodes: &[Opcode], modes: &[ParamMode]) -> bool {
+        self.opcodes.iter().all(|opcode| opcodes.contains(opcode)) && self.modes.iter().all(|mode| modes.contains(mode))
+    }
+
+    fn fits(&self, min: i64, max: i64) -> bool {
+        min <= self.smallest && self.largest <= max
+    }
+}
+
+fn implementations() -> Vec<Implementation> {
+    vec![
+        Implementation {
+            name: "day2 computer",
+            supports: |usage, program| {
+                usage.only(&PART_ONE, &[ParamMode::Positional]) && usage.fits(0, u32::MAX as i64) && usage.memory_used <= program.len()
+            },
+            run: |program, _, _| {
+                let memory = computer(program.iter().map(|value| *value as u32).collect());
+                Outcome { outputs: vec![], memory: memory.into_iter().map(i64::from).collect() }
+            },
+        },
+        Implementation {
+            name: "day5 computer_ver2",
+            supports: |usage, program| {
+                usage.only(&PART_TWO, &[ParamMode::Positional, ParamMode::Immediate]) && usage.fits(i32::MIN as i64, i32::MAX as i64)
+                    && usage.memory_used <= program.len()
+            },
+            run: |program, inputs, _| {
+                let mut inputs = inputs.iter();
+                let mut outputs = vec![];
+                let memory = computer_ver2_with_io(program.iter().map(|value| *value as i32).collect(),
+                                                   || *inputs.next().unwrap() as i32,
+                                                   |value| outputs.push(i64::from(value)));
+                Outcome { outputs, memory: memory.into_iter().map(i64::from).collect() }
+            },
+        },
+        Implementation {
+            name: "day7 computer_ver3",
+            supports: |usage, program| {
+                usage.only(&PART_TWO, &[ParamMode::Positional, ParamMode::Immediate]) && usage.fits(i32::MIN as i64, i32::MAX as i64)
+                    && usage.memory_used <= program.len()
+            },
+            run: |program, inputs, _| {
+                let mut outputs = vec![];
+                let memory = computer_ver3_with_memory(program.iter().map(|value| *value as i32).collect(),
+                                                       inputs.iter().map(|value| *value as i32).collect(),
+                                                       &mut outputs);
+                Outcome { outputs: outputs.into_iter().map(i64::from).collect(), memory: memory.into_iter().map(i64::from).collect() }
+            },
+        },
+        Implementation {
+            name: "day9 computer_ver5",
+            supports: |usage, program| usage.memory_used <= program.len() + EXTRA_MEMORY,
+            run: |program, inputs, expected| {
+                let (input_send, input_recv) = sync_channel(inputs.len().max(1));
+                let (output_send, output_recv) = sync_channel(expected.outputs.len().max(1));
+                for value in inputs {
+                    input_send.send(*value).unwrap();
+                }
+                let (_, memory) = computer_ver5_with_memory(program.to_vec(), output_send, input_recv);
+                Outcome { outputs: output_recv.try_iter().collect(), memory }
+            },
+        },
+        Implementation {
+            name: "day13 Computer::run",
+            supports: |usage, program| usage.memory_used <= program.len() + EXTRA_MEMORY,
+            run: |program, inputs, expected| {
+                let (input_send, input_recv) = sync_channel(inputs.len().max(1));
+                let (output_send, output_recv) = sync_channel(expected.outputs.len().max(1));
+                for value in inputs {
+                    input_send.send(*value).unwrap();
+                }
+                let mut computer = Computer::new(program.to_vec(), EXTRA_MEMORY, output_send, input_recv);
+                computer.run();
+                Outcome { outputs: output_recv.try_iter().collect(), memory: computer.memory }
+            },
+        },
+        Implementation {
+            name: "compiled",
+            supports: |_, _| true,
+            run: |program, inputs, _| {
+                let mut compiled = CompiledVm::new(Vm::with_inputs(program.to_vec(), inputs));
+                compiled.run().unwrap();
+                Outcome { outputs: compiled.vm.outputs, memory: compiled.vm.memory }
+            },
+        },
+    ]
+}
+
+fn reference(program: &[i64], inputs: &[i64]) -> (Outcome, Usage) {
+    let mut vm = Vm::with_inputs(program.to_vec(), inputs);
+    let mut usage = Usage { opcodes: HashSet::new(), modes: HashSet::new(), memory_used: 0, smallest: 0, largest: 0 };
+    for value in program.iter().chain(inputs) {
+        usage.smallest = usage.smallest.min(*value);
+        usage.largest = usage.largest.max(*value);
+    }
+    while let Some(step) = vm.step().unwrap() {
+        let instruction = step.instruction;
+        usage.opcodes.insert(instruction.opcode);
+        usage.modes.extend(&instruction.modes[..instruction.opcode.arity()]);
+        let mut touched = vec![step.instruction_pointer + instruction.size() - 1];
+        touched.extend(step.reads.iter().flatten());
+        touched.extend(step.write.map(|write| write.address));
+        usage.memory_used = touched.into_iter().fold(usage.memory_used, |used, address| used.max(address + 1));
+        for value in step.operands[..instruction.opcode.arity()].iter().chain(step.write.map(|write| write.new).as_ref()) {
+            usage.smallest = usage.smallest.min(*value);
+            usage.largest = usage.largest.max(*value);
+        }
+    }
+    assert_eq!(vm.status(), Status::Halted, "differential programs must halt with the inputs given");
+    (Outcome { outputs: vm.outputs, memory: vm.memory }, usage)
+}
+
+fn trimmed(memory: &[i64]) -> &[i64] {
+    let end = memory.iter().rposition(|value| *value != 0).map_or(0, |last| last + 1);
+    &memory[..end]
+}
+
+// returns the names of the implementations that ran the program, all of them in agreement
+fn check(program: &[i64], inputs: &[i64]) -> Vec<&'static str> {
+    let (expected, usage) = reference(program, inputs);
+    let mut ran = vec![];
+    for implementation in implementations() {
+        if !(implementation.supports)(&usage, program) {
+            continue;
+        }
+        let outcome = (implementation.run)(program, inputs, &expected);
+        assert_eq!(outcome.outputs, expected.outputs, "{} disagrees on outputs", implementation.name);
+        // implementations allocate differently, but all memory starts zeroed
+        assert_eq!(trimmed(&outcome.memory), trimmed(&expected.memory), "{} disagrees on final memory", implementation.name);
+        ran.push(implementation.name);
+    }
+    ran
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day2_programs() {
+        assert_eq!(check(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]).len(), 6);
+        assert_eq!(check(&[1, 1, 1, 4, 99, 5, 6, 0, 99], &[]).len(), 6);
+
+        let mut program = load_program("./inputs/day2").unwrap();
+        program[1] = 12;
+        program[2] = 2;
+        assert_eq!(check(&program, &[]).len(), 6);
+    }
+
+    #[test]
+    fn test_day5_programs() {
+        let compare = vec![3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+                           1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+                           1105, 1, 46, 98, 99];
+        for input in [7, 8, 9] {
+            assert_eq!(check(&compare, &[input]), vec!["day5 computer_ver2", "day7 computer_ver3", "day9 computer_ver5", "day13 Computer::run", "compiled"]);
+        }
+        // negative immediates and a self-modifying multiply
+        assert_eq!(check(&[1101, 100, -1, 4, 0], &[]).len(), 5);
+        assert_eq!(check(&[1002, 4, 3, 4, 33], &[]).len(), 5);
+
+        let program = load_program("./inputs/day5").unwrap();
+        assert_eq!(check(&program, &[1]).len(), 5);
+        assert_eq!(check(&program, &[5]).len(), 5);
+    }
+
+    #[test]
+    fn test_day7_programs() {
+        let program = vec![3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23, 99, 0, 0];
+        let mut signal = 0;
+        for phase in [0, 1, 2, 3, 4] {
+            assert_eq!(check(&program, &[phase, signal]).len(), 5);
+            signal = reference(&program, &[phase, signal]).0.outputs[0];
+        }
+        assert_eq!(signal, 54321);
+    }
+
+    #[test]
+    fn test_day9_programs() {
+        let quine = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+        assert_eq!(check(&quine, &[]), vec!["day9 computer_ver5", "day13 Computer::run", "compiled"]);
+        assert_eq!(check(&[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[]).len(), 3);
+        assert_eq!(check(&[104, 1125899906842624, 99], &[]).len(), 3);
+
+        let program = load_program("./inputs/day9").unwrap();
+        assert_eq!(check(&program, &[1]).len(), 3);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::intcode::analysis::{Analysis, CellKind};
+use crate::intcode::{Instruction, ParamMode};
+
+fn format_operand(mode: ParamMode, value: i64) -> String {
+    match mode {
+        ParamMode::Positional => format!("[{value}]"),
+        ParamMode::Immediate => format!("{value}"),
+        ParamMode::Relative if value == 0 => "[rb]".to_string(),
+        ParamMode::Relative if value < 0 => format!("[rb{value}]"),
+        ParamMode::Relative => format!("[rb+{value}]"),
+    }
+}
+
+// Decodes the instruction at `address`, returning its assembler text and size. Cells that
+// don't hold a canonical instruction with all of its parameters present give None.
+pub fn disassemble_instruction(memory: &[i64], address: usize) -> Option<(String, usize)> {
+    let instruction = Instruction::decode(*memory.get(address)?).ok()?;
+    if !instruction.is_canonical() || address + instruction.size() > memory.len() {
+        return None;
+    }
+    let operands: Vec<String> = (0..instruction.opcode.arity())
+        .map(|index| format_operand(instruction.modes[index], memory[address + 1 + index]))
+        .collect();
+    let text = if operands.is_empty() {
+        format!("{:?}", instruction.opcode)
+    } else {
+        format!("{:?} {}", instruction.opcode, operands.join(", "))
+    };
+    Some((text, instruction.size()))
+}
+
+// Linear sweep from address 0. The output is valid `asm::assemble` input that reproduces
+// the program exactly, with the address of each line in a trailing comment.
+pub fn disassemble(program: &[i64]) -> String {
+    let mut output = String::new();
+    let mut address = 0;
+    while address < program.len() {
+        let (text, size) = disassemble_instruction(program, address)
+            .unwrap_or_else(|| (format!(".data {}", program[address]), 1));
+        output += &format!("{:<40}; {}\n", text, address);
+        address += size;
+    }
+    output
+}
+
+// Like `disassemble`, but only decodes the cells `analysis` found to start an instruction,
+// so data that happens to look like code stays `.data`.
+pub fn disassemble_analysed(program: &[i64], analysis: &Analysis) -> String {
+    let mut output = String::new();
+    let mut address = 0;
+    while address < program.len() {
+        let (text, size) = disassemble_instruction(program, address)
+            .filter(|_| analysis.kinds[address] == CellKind::Opcode)
+            .unwrap_or_else(|| (format!(".data {}", program[address]), 1));
+        output += &format!("{:<40}; {}\n", text, address);
+        address += size;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::analysis::analyse;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![1002, 4, 3, 4, 33, 109, -2, 22201, 0, 1, 2];
+        let text = disassemble(&program);
+        let lines: Vec<_> = text.lines().map(|line| line.split(';').next().unwrap().trim()).collect();
+        assert_eq!(lines, vec![
+            "Multiply [4], 3, [4]",
+            ".data 33",
+            "RelativeBaseOffset -2",
+            "Sum [rb], [rb+1], [rb+2]",
+        ]);
+    }
+
+    #[test]
+    fn test_disassemble_analysed() {
+        // the 1 and 2 after Stop would sweep as `Sum [2], [0], [0]`
+        let program = vec![1105, 1, 4, 99, 99, 1, 2, 0, 0];
+        let text = disassemble_analysed(&program, &analyse(&program));
+        let lines: Vec<_> = text.lines().map(|line| line.split(';').next().unwrap().trim()).collect();
+        assert_eq!(lines, vec!["JumpIfTrue 1, 4", ".data 99", "Stop", ".data 1", ".data 2", ".data 0", ".data 0"]);
+        assert_eq!(assemble(&text).unwrap(), program);
+    }
+
+    #[test]
+    fn test_truncated_instruction() {
+        assert_eq!(disassemble_instruction(&[1, 0, 0], 0), None);
+        assert_eq!(disassemble_instruction(&[99], 0), Some(("Stop".to_string(), 1)));
+        assert_eq!(disassemble_instruction(&[1104, 1], 0), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::ops::Range;
+
+use crate::intcode::analysis::{analyse, CellKind};
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::vm::Vm;
+use crate::intcode::{parse_program, parse_range, snapshot};
+
+// One row per cell. Only cells that static analysis of `memory` finds to start an
+// instruction get disassembled; operands are marked so a table of code still lines up.
+pub fn dump(memory: &[i64], range: Range<usize>) -> String {
+    let analysis = analyse(memory);
+    let mut lines = vec![format!("{:>6}  {:>14}  instruction", "addr", "value")];
+    for address in range.start..range.end.min(memory.len()) {
+        let text = match analysis.kinds[address] {
+            CellKind::Opcode => disassemble_instruction(memory, address).map(|(text, _)| text).unwrap_or_default(),
+            CellKind::Operand => "  |".to_string(),
+            CellKind::Data => String::new(),
+        };
+        lines.push(format!("{:>6}  {:>14}  {}", address, memory[address], text).trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub address: usize,
+    pub before: i64,
+    pub after: i64,
+}
+
+// every cell that differs, with memory past the end of the shorter one reading as zero
+pub fn diff_memory(before: &[i64], after: &[i64]) -> Vec<Change> {
+    let read = |memory: &[i64], address: usize| memory.get(address).copied().unwrap_or(0);
+    (0..before.len().max(after.len()))
+        .map(|address| Change { address, before: read(before, address), after: read(after, address) })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+// Registers that moved, then the changed cells with their disassembly in `after`, which is
+// how a score or a ball position shows up between two frames of a game.
+pub fn diff(before: &Vm, after: &Vm) -> String {
+    let mut lines = vec![];
+    if before.instruction_pointer != after.instruction_pointer {
+        lines.push(format!("ip {} -> {}", before.instruction_pointer, after.instruction_pointer));
+    }
+    if before.relative_base != after.relative_base {
+        lines.push(format!("rb {} -> {}", before.relative_base, after.relative_base));
+    }
+    if after.outputs.starts_with(&before.outputs) && after.outputs.len() > before.outputs.len() {
+        lines.push(format!("{} new outputs", after.outputs.len() - before.outputs.len()));
+    }
+    let changes = diff_memory(&before.memory, &after.memory);
+    lines.push(format!("{} cells changed", changes.len()));
+    let analysis = analyse(&after.memory);
+    for change in changes {
+        let code = analysis.kinds.get(change.address).is_some_and(|kind| *kind != CellKind::Data);
+        let delta = change.after.checked_sub(change.before).map(|delta| format!("{delta:+}")).unwrap_or_default();
+        lines.push(format!("{:>6}  {:>14} -> {:<14} {:>8}{}", change.address, change.before, change.after, delta, if code { "  (code)" } else { "" }));
+    }
+    lines.join("\n")
+}
+
+// a snapshot, or a plain program loaded into a fresh VM
+pub fn load_vm(path: &str) -> Result<Vm, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    if snapshot::is_snapshot(&text) {
+        snapshot::decode(&text)
+    } else {
+        parse_program(&text).map(Vm::new)
+    }
+}
+
+// `dump <program or snapshot file> [a..b]`
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: dump <program or snapshot file> [a..b]");
+        return;
+    };
+    let result = load_vm(path).and_then(|vm| {
+        let range = args.get(1).map(|range| parse_range(range)).transpose()?.unwrap_or(0..usize::MAX);
+        Ok(dump(&vm.memory, range))
+    });
+    match result {
+        Ok(table) => println!("{table}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+// `memdiff <before> <after>`, each a program or snapshot file
+pub fn diff_main(args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("usage: memdiff <before> <after>");
+        return;
+    }
+    match load_vm(&args[0]).and_then(|before| Ok(diff(&before, &load_vm(&args[1])?))) {
+        Ok(report) => println!("{report}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program;
+    use crate::intcode::patch::free_play;
+    use crate::intcode::vm::Status;
+
+    #[test]
+    fn test_dump() {
+        let table = dump(&[1002, 4, 3, 4, 33, 99], 0..10);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[0], "  addr           value  instruction");
+        assert_eq!(lines[1], "     0            1002  Multiply [4], 3, [4]");
+        assert_eq!(lines[2], "     1               4    |");
+        assert_eq!(lines[5], "     4              33");
+        assert_eq!(lines.len(), 7);
+        assert_eq!(dump(&[1002, 4, 3, 4, 33, 99], 4..5).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_diff_memory() {
+        assert_eq!(diff_memory(&[1, 2, 3], &[1, 5, 3, 0, 7]), vec![
+            Change { address: 1, before: 2, after: 5 },
+            Change { address: 4, before: 0, after: 7 },
+        ]);
+        assert!(diff_memory(&[1, 0], &[1]).is_empty());
+    }
+
+    #[test]
+    fn test_breakout_frame() {
+        // play one frame of day13 and find the cells holding the ball's position
+        let mut vm = free_play().vm(load_program("./inputs/day13").unwrap()).unwrap();
+        assert_eq!(vm.run().unwrap(), Status::NeedsInput);
+        let ball = |outputs: &[i64]| outputs.chunks(3).rev().find(|tile| tile[2] == 4).unwrap()[0];
+        let before = vm.clone();
+        let x = ball(&before.outputs);
+        vm.push_input(0);
+        assert_eq!(vm.run().unwrap(), Status::NeedsInput);
+        let next_x = ball(&vm.outputs[before.outputs.len()..]);
+        assert_ne!(x, next_x);
+
+        let changes = diff_memory(&before.memory, &vm.memory);
+        assert!(changes.iter().any(|change| change.before == x && change.after == next_x));
+        let report = diff(&before, &vm);
+        assert!(report.contains(&format!("{} cells changed", changes.len())));
+        assert!(report.contains("new outputs"));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+
+use crate::intcode::vm::{Step, Vm};
+
+const DEFAULT_SNAPSHOT_INTERVAL: usize = 10_000;
+const DEFAULT_CAPACITY: usize = 200_000;
+
+// What it takes to run a VM backwards: an undo log with one `Step` per executed
+// instruction, and a full copy of the VM every `interval` instructions so that jumping far
+// back doesn't have to undo every instruction in between. Outputs are assumed to stay in
+// `vm.outputs`, the way the debugger leaves them, and memory that grew is left grown.
+//
+// Only the last `capacity` instructions are kept; older ones and their snapshots are
+// dropped as new ones are recorded, so a long run doesn't keep growing memory. Step
+// numbers keep counting from the start of the run either way.
+pub struct History {
+    steps: VecDeque<Step>,
+    // number of instructions dropped off the front of `steps`
+    dropped: usize,
+    // (instructions executed so far, VM state at that point)
+    snapshots: VecDeque<(usize, Vm)>,
+    interval: usize,
+    capacity: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOT_INTERVAL, DEFAULT_CAPACITY)
+    }
+}
+
+fn undo_step(vm: &mut Vm, step: &Step) {
+    vm.instruction_pointer = step.instruction_pointer;
+    vm.halted = false;
+    if let Some(write) = step.write {
+        vm.memory[write.address] = write.old;
+    }
+    if let Some((old, _)) = step.relative_base {
+        vm.relative_base = old;
+    }
+    if let Some(value) = step.input {
+        vm.inputs.push_front(value);
+    }
+    if step.output.is_some() {
+        vm.outputs.pop();
+    }
+}
+
+impl History {
+    pub fn new(interval: usize, capacity: usize) -> Self {
+        Self { steps: VecDeque::new(), dropped: 0, snapshots: VecDeque::new(), interval: interval.max(1), capacity: capacity.max(1) }
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // number of instructions executed since the history started
+    pub fn position(&self) -> usize {
+        self.dropped + self.steps.len()
+    }
+
+    // the earliest step that can still be gone back to
+    pub fn start(&self) -> usize {
+        self.dropped
+    }
+
+    // the kept steps, each with the number of instructions executed before it
+    pub fn steps(&self) -> impl Iterator<Item = (usize, &Step)> {
+        self.steps.iter().enumerate().map(|(index, step)| (self.dropped + index, step))
+    }
+
+    // forget everything, e.g. after the state was edited by hand and can no longer be undone
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.snapshots.clear();
+        self.dropped = 0;
+    }
+
+    // `vm` is the state right after `step` executed
+    pub fn record(&mut self, vm: &Vm, step: Step) {
+        self.steps.push_back(step);
+        if self.position().is_multiple_of(self.interval) {
+            self.snapshots.push_back((self.position(), vm.clone()));
+        }
+        if self.steps.len() > self.capacity {
+            self.steps.pop_front();
+            self.dropped += 1;
+            while self.snapshots.front().is_some_and(|(at, _)| *at < self.dropped) {
+                self.snapshots.pop_front();
+            }
+        }
+    }
+
+    pub fn undo(&mut self, vm: &mut Vm) -> Option<Step> {
+        let step = self.steps.pop_back()?;
+        undo_step(vm, &step);
+        if self.snapshots.back().is_some_and(|(at, _)| *at > self.position()) {
+            self.snapshots.pop_back();
+        }
+        Some(step)
+    }
+
+    // Takes `vm` back to how it was after `target` instructions, starting from whichever is
+    // closer: the present, undoing one instruction at a time, or an earlier snapshot, redoing
+    // the logged writes from there.
+    pub fn rewind(&mut self, vm: &mut Vm, target: usize) -> Result<(), String> {
+        let current = self.position();
+        if target > current {
+            return Err(format!("can't go back to step {target}, only {current} executed"));
+        }
+        if target < self.dropped {
+            return Err(format!("can't go back to step {target}, the history starts at step {}", self.dropped));
+        }
+        let index = |step: usize| step - self.dropped;
+        match self.snapshots.iter().rev().find(|(at, _)| *at <= target) {
+            Some((at, snapshot)) if target - at < current - target => {
+                let mut restored = snapshot.clone();
+                for step in self.steps.range(index(*at)..index(target)) {
+                    if let Some(write) = step.write {
+                        restored.write(write.address, write.new)?;
+                    }
+                    if let Some((_, new)) = step.relative_base {
+                        restored.relative_base = new;
+                    }
+                }
+                restored.instruction_pointer = self.steps[index(target)].instruction_pointer;
+                restored.halted = false;
+                // input may have been queued since the snapshot, so work back from the present
+                restored.inputs = vm.inputs.clone();
+                restored.outputs = vm.outputs.clone();
+                for step in self.steps.range(index(target)..).rev() {
+                    if let Some(value) = step.input {
+                        restored.inputs.push_front(value);
+                    }
+                    if step.output.is_some() {
+                        restored.outputs.pop();
+                    }
+                }
+                *vm = restored;
+                self.steps.truncate(index(target));
+                self.snapshots.retain(|(at, _)| *at <= target);
+            }
+            _ => {
+                while self.position() > target {
+                    self.undo(vm);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::vm::Status;
+
+    fn create_vec() -> Vec<i64> {
+        // reads a count, then outputs it while counting down, moving the relative base along
+        vec![3, 100, 4, 100, 109, 1, 1001, 100, -1, 100, 1005, 100, 2, 99]
+    }
+
+    fn run(vm: &mut Vm, history: &mut History) {
+        while let Some(step) = vm.step().unwrap() {
+            history.record(vm, step);
+        }
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut vm = Vm::with_inputs(create_vec(), &[3]);
+        let start = vm.clone();
+        let mut history = History::new(4, 1000);
+        run(&mut vm, &mut history);
+        assert_eq!(vm.status(), Status::Halted);
+        assert_eq!(vm.outputs, vec![3, 2, 1]);
+        assert_eq!(history.position(), 1 + 3 * 4 + 1);
+
+        let step = history.undo(&mut vm).unwrap();
+        assert_eq!(step.instruction_pointer, 13);
+        assert!(!vm.halted);
+        history.undo(&mut vm).unwrap();
+        history.undo(&mut vm).unwrap();
+        assert_eq!(vm.read(100), 1);
+        assert_eq!(vm.relative_base, 3);
+
+        // memory grown by a write stays grown, but reads as zero again
+        history.rewind(&mut vm, 0).unwrap();
+        vm.memory.truncate(start.memory.len());
+        assert_eq!(vm, start);
+        assert!(history.undo(&mut vm).is_none());
+    }
+
+    #[test]
+    fn test_rewind_from_snapshot() {
+        let mut vm = Vm::with_inputs(create_vec(), &[20]);
+        let mut history = History::new(8, 1000);
+        run(&mut vm, &mut history);
+
+        // step 18 is much closer to the snapshot at 16 than to the end at 82
+        let mut expected = Vm::with_inputs(create_vec(), &[20]);
+        for _ in 0..18 {
+            expected.step().unwrap();
+        }
+        history.rewind(&mut vm, 18).unwrap();
+        assert_eq!(vm, expected);
+        assert_eq!(history.position(), 18);
+        assert!(history.rewind(&mut vm, 19).is_err());
+
+        // running forward again gives the same result as the first time
+        run(&mut vm, &mut history);
+        assert_eq!(vm.outputs, (1..=20).rev().collect::<Vec<_>>());
+        assert_eq!(history.position(), 82);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut vm = Vm::with_inputs(create_vec(), &[20]);
+        let mut history = History::new(8, 20);
+        run(&mut vm, &mut history);
+        assert_eq!(history.position(), 82);
+        assert_eq!(history.start(), 62);
+        assert_eq!(history.steps().count(), 20);
+        assert!(history.snapshots.iter().all(|(at, _)| *at >= 62));
+        assert!(history.rewind(&mut vm, 61).unwrap_err().contains("starts at step 62"));
+
+        let mut expected = Vm::with_inputs(create_vec(), &[20]);
+        for _ in 0..65 {
+            expected.step().unwrap();
+        }
+        history.rewind(&mut vm, 65).unwrap();
+        assert_eq!(vm, expected);
+        history.rewind(&mut vm, 62).unwrap();
+        assert!(history.undo(&mut vm).is_none());
+        assert_eq!(history.position(), 62);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write as _};
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+
+use crate::intcode::load_program;
+use crate::intcode::vm::{Status, Vm};
+
+// Where a VM's input comes from. `Ok(None)` means nothing more is coming, and the VM is
+// left waiting on input.
+pub trait Input {
+    fn read(&mut self) -> Result<Option<i64>, String>;
+}
+
+pub trait Output {
+    fn write(&mut self, value: i64) -> Result<(), String>;
+}
+
+impl Input for VecDeque<i64> {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        Ok(self.pop_front())
+    }
+}
+
+impl Output for Vec<i64> {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+pub struct InputFn<F: FnMut() -> Option<i64>>(pub F);
+
+impl<F: FnMut() -> Option<i64>> Input for InputFn<F> {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        Ok((self.0)())
+    }
+}
+
+pub struct OutputFn<F: FnMut(i64)>(pub F);
+
+impl<F: FnMut(i64)> Output for OutputFn<F> {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+// blocks until a value arrives; a disconnected sender ends the input
+impl Input for Receiver<i64> {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        Ok(self.recv().ok())
+    }
+}
+
+impl Output for Sender<i64> {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        self.send(value).map_err(|e| format!("output channel closed: {e}"))
+    }
+}
+
+impl Output for SyncSender<i64> {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        self.send(value).map_err(|e| format!("output channel closed: {e}"))
+    }
+}
+
+// one line from the terminal without its line ending, None at end of file
+pub fn read_line(prompt: Option<&str>) -> Result<Option<String>, String> {
+    if let Some(prompt) = prompt {
+        println!("{prompt}");
+    }
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+// one number per line, optionally prompting first
+pub struct StdinInput {
+    pub prompt: Option<String>,
+}
+
+impl Input for StdinInput {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        let Some(line) = read_line(self.prompt.as_deref())? else { return Ok(None) };
+        line.trim().parse::<i64>().map(Some).map_err(|_| format!("invalid input {:?}", line.trim()))
+    }
+}
+
+pub struct StdoutOutput;
+
+impl Output for StdoutOutput {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        println!("{value}");
+        Ok(())
+    }
+}
+
+// values separated by commas or newlines, read up front
+pub struct FileInput {
+    values: VecDeque<i64>,
+}
+
+impl FileInput {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Ok(Self { values: load_program(path)?.into() })
+    }
+}
+
+impl Input for FileInput {
+    fn read(&mut self) -> Result<Option<i64>, String> {
+        self.values.read()
+    }
+}
+
+// one value per line
+pub struct FileOutput {
+    writer: BufWriter<File>,
+}
+
+impl FileOutput {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+}
+
+impl Output for FileOutput {
+    fn write(&mut self, value: i64) -> Result<(), String> {
+        writeln!(self.writer, "{value}").map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for FileOutput {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// Runs `vm` until it halts or `input` runs dry, passing each output on as soon as it is
+// produced so the other side of a channel can react before the VM asks for more input.
+pub fn run_io(vm: &mut Vm, input: &mut impl Input, output: &mut impl Output) -> Result<Status, String> {
+    loop {
+        while let Some(step) = vm.step()? {
+            if step.output.is_some() {
+                for value in vm.take_outputs() {
+                    output.write(value)?;
+                }
+            }
+        }
+        match vm.status() {
+            Status::NeedsInput => match input.read()? {
+                Some(value) => vm.push_input(value),
+                None => return Ok(Status::NeedsInput),
+            },
+            status => return Ok(status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{channel, sync_channel};
+    use std::{env, fs, thread};
+
+    fn create_vec() -> Vec<i64> {
+        // outputs the sum of each pair of inputs
+        vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 1105, 1, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_queue_io() {
+        let mut vm = Vm::new(create_vec());
+        let mut input = VecDeque::from([1, 2, 3, 4, 5]);
+        let mut output = vec![];
+        assert_eq!(run_io(&mut vm, &mut input, &mut output).unwrap(), Status::NeedsInput);
+        assert_eq!(output, vec![3, 7]);
+        // the fifth value was consumed, the VM is waiting for its partner
+        assert!(input.is_empty());
+        assert_eq!(vm.read(13), 5);
+    }
+
+    #[test]
+    fn test_closure_io() {
+        let mut counter = 0;
+        let mut sums = vec![];
+        let mut vm = Vm::new(create_vec());
+        let status = run_io(&mut vm, &mut InputFn(|| { counter += 1; if counter <= 4 { Some(counter) } else { None } }),
+                            &mut OutputFn(|value| sums.push(value))).unwrap();
+        assert_eq!(status, Status::NeedsInput);
+        assert_eq!(sums, vec![3, 7]);
+    }
+
+    #[test]
+    fn test_channel_io() {
+        let (input_send, mut input_recv) = channel();
+        let (mut output_send, output_recv) = sync_channel(1);
+        let handle = thread::spawn(move || {
+            let mut vm = Vm::new(create_vec());
+            run_io(&mut vm, &mut input_recv, &mut output_send)
+        });
+        // feed back each sum as the first value of the next pair
+        input_send.send(1).unwrap();
+        input_send.send(1).unwrap();
+        let mut sums = vec![];
+        for _ in 0..4 {
+            let sum = output_recv.recv().unwrap();
+            sums.push(sum);
+            input_send.send(sum).unwrap();
+            input_send.send(1).unwrap();
+        }
+        drop(input_send);
+        assert_eq!(handle.join().unwrap().unwrap(), Status::NeedsInput);
+        assert_eq!(sums, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_file_io() {
+        let input_path = env::temp_dir().join("intcode_io_input_test.txt");
+        let output_path = env::temp_dir().join("intcode_io_output_test.txt");
+        fs::write(&input_path, "10,20\n30\n40\n").unwrap();
+        {
+            let mut input = FileInput::open(input_path.to_str().unwrap()).unwrap();
+            let mut output = FileOutput::create(output_path.to_str().unwrap()).unwrap();
+            run_io(&mut Vm::new(create_vec()), &mut input, &mut output).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), "30\n70\n");
+        fs::remove_file(input_path).unwrap();
+        fs::remove_file(output_path).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+
+use crate::intcode::vm::Vm;
+
+// A named list of memory writes made before a program starts, such as the noun and verb
+// of day2 or the coin that puts day13 in free play.
+//
+//   PatchSet::new("noun-verb").set(1, 12).set(2, 2).vm(program)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchSet {
+    pub name: String,
+    pub description: String,
+    pub patches: Vec<(usize, i64)>,
+}
+
+impl PatchSet {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Self::default() }
+    }
+
+    pub fn describe(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn set(mut self, address: usize, value: i64) -> Self {
+        self.patches.push((address, value));
+        self
+    }
+
+    // Patches only replace cells the program already has; one past the end is almost
+    // always a set meant for a different program.
+    pub fn validate(&self, program: &[i64]) -> Result<(), String> {
+        match self.patches.iter().find(|(address, _)| *address >= program.len()) {
+            Some((address, _)) => Err(format!("patch set {}: address {} is outside the program ({} cells)", self.name, address, program.len())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, program: &mut [i64]) -> Result<(), String> {
+        self.validate(program)?;
+        for (address, value) in &self.patches {
+            program[*address] = *value;
+        }
+        Ok(())
+    }
+
+    pub fn vm(&self, mut program: Vec<i64>) -> Result<Vm, String> {
+        self.apply(&mut program)?;
+        Ok(Vm::new(program))
+    }
+}
+
+// the same sets as in ./inputs/patches
+pub fn alarm_1202() -> PatchSet {
+    PatchSet::new("1202-alarm")
+        .describe("day2 part one: restore the gravity assist program to the 1202 program alarm state")
+        .set(1, 12)
+        .set(2, 2)
+}
+
+pub fn free_play() -> PatchSet {
+    PatchSet::new("free-play").describe("day13 part two: two quarters inserted").set(0, 2)
+}
+
+// One set per `[name]` header, each followed by `address = value` lines. Text after `#`
+// is a comment; a comment directly under a header describes the set.
+//
+//   [free-play]
+//   # day13 with two quarters inserted
+//   0 = 2
+pub fn parse(text: &str) -> Result<Vec<PatchSet>, String> {
+    let mut sets: Vec<PatchSet> = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let (line, comment) = line.split_once('#').unwrap_or((line, ""));
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty() || sets.iter().any(|set| set.name == name) {
+                return Err(format!("line {}: invalid or repeated patch set name {:?}", number + 1, name));
+            }
+            sets.push(PatchSet::new(name));
+        } else if line.is_empty() {
+            if let Some(set) = sets.last_mut().filter(|set| set.patches.is_empty() && set.description.is_empty()) {
+                set.description = comment.trim().to_string();
+            }
+        } else {
+            let set = sets.last_mut().ok_or_else(|| format!("line {}: patch before any [name]", number + 1))?;
+            let (address, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected address = value", number + 1))?;
+            let address = address.trim().parse::<usize>().map_err(|_| format!("line {}: invalid address {:?}", number + 1, address.trim()))?;
+            let value = value.trim().parse::<i64>().map_err(|_| format!("line {}: invalid value {:?}", number + 1, value.trim()))?;
+            set.patches.push((address, value));
+        }
+    }
+    Ok(sets)
+}
+
+pub fn load(path: &str) -> Result<Vec<PatchSet>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    parse(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+// `file:name`, the form the command line tools take
+pub fn load_set(spec: &str) -> Result<PatchSet, String> {
+    let (path, name) = spec.rsplit_once(':').ok_or_else(|| format!("invalid patch set {spec:?}, expected file:name"))?;
+    load(path)?.into_iter().find(|set| set.name == name).ok_or_else(|| format!("{path}: no patch set named {name:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program;
+    use crate::intcode::vm::Status;
+
+    fn create_vec() -> Vec<i64> {
+        vec![1, 0, 0, 0, 99]
+    }
+
+    #[test]
+    fn test_apply() {
+        let set = PatchSet::new("double").set(1, 4).set(2, 4);
+        let mut vm = set.vm(create_vec()).unwrap();
+        assert_eq!(vm.run().unwrap(), Status::Halted);
+        assert_eq!(vm.read(0), 198);
+
+        let err = PatchSet::new("far").set(5, 1).vm(create_vec()).unwrap_err();
+        assert_eq!(err, "patch set far: address 5 is outside the program (5 cells)");
+    }
+
+    #[test]
+    fn test_parse() {
+        let sets = parse("# patches\n[a]\n# the first one\n1 = 4\n2=4 # again\n\n[b]\n0 = -1\n").unwrap();
+        assert_eq!(sets, vec![
+            PatchSet::new("a").describe("the first one").set(1, 4).set(2, 4),
+            PatchSet::new("b").set(0, -1),
+        ]);
+        assert_eq!(parse("1 = 2").unwrap_err(), "line 1: patch before any [name]");
+        assert_eq!(parse("[a]\n1 2").unwrap_err(), "line 2: expected address = value");
+        assert_eq!(parse("[a]\nx = 2").unwrap_err(), "line 2: invalid address \"x\"");
+        assert!(parse("[a]\n[a]").is_err());
+    }
+
+    #[test]
+    fn test_patch_file() {
+        assert_eq!(load_set("./inputs/patches:1202-alarm").unwrap(), alarm_1202());
+        assert_eq!(load_set("./inputs/patches:free-play").unwrap(), free_play());
+        assert!(load_set("./inputs/patches:missing").unwrap_err().contains("no patch set named"));
+
+        let program = load_program("./inputs/day13").unwrap();
+        let mut vm = free_play().vm(program).unwrap();
+        assert_eq!(vm.run().unwrap(), Status::NeedsInput);
+    }
+}
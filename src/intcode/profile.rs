@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::vm::{Limits, Observer, Status, Step, Vm};
+use crate::intcode::{load_program, parse_inputs, Opcode};
+
+const DEFAULT_REGION_SIZE: usize = 64;
+
+// Execution counts gathered from a run. Memory traffic is bucketed into fixed size regions
+// so a report on a large program stays readable.
+pub struct Profiler {
+    pub steps: u64,
+    pub opcodes: HashMap<Opcode, u64>,
+    pub addresses: HashMap<usize, u64>,
+    pub input_waits: u64,
+    // region index -> (reads, writes)
+    pub regions: HashMap<usize, (u64, u64)>,
+    region_size: usize,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(DEFAULT_REGION_SIZE)
+    }
+}
+
+impl Profiler {
+    pub fn new(region_size: usize) -> Self {
+        Self { steps: 0, opcodes: HashMap::new(), addresses: HashMap::new(), input_waits: 0, regions: HashMap::new(), region_size: region_size.max(1) }
+    }
+
+    pub fn record(&mut self, step: &Step) {
+        self.steps += 1;
+        *self.opcodes.entry(step.instruction.opcode).or_insert(0) += 1;
+        *self.addresses.entry(step.instruction_pointer).or_insert(0) += 1;
+        for address in step.reads.iter().flatten() {
+            self.regions.entry(address / self.region_size).or_insert((0, 0)).0 += 1;
+        }
+        if let Some(write) = step.write {
+            self.regions.entry(write.address / self.region_size).or_insert((0, 0)).1 += 1;
+        }
+    }
+
+    // Hot spots first. Addresses are shown with their disassembly in `vm`'s current memory,
+    // which may differ from what ran if the program modified itself.
+    pub fn report(&self, vm: &Vm, top: usize) -> String {
+        let percent = |count: u64| count as f64 * 100.0 / self.steps.max(1) as f64;
+        let mut lines = vec![format!("{} instructions executed, {} input waits", self.steps, self.input_waits)];
+
+        lines.push("\nopcodes:".to_string());
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then((*a.0 as u8).cmp(&(*b.0 as u8))));
+        for (opcode, count) in opcodes {
+            lines.push(format!("{:>12} {:>6.2}%  {:?}", count, percent(*count), opcode));
+        }
+
+        lines.push(format!("\nhottest {top} addresses:"));
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (address, count) in addresses.into_iter().take(top) {
+            let text = disassemble_instruction(&vm.memory, *address).map(|(text, _)| text).unwrap_or_default();
+            lines.push(format!("{:>12} {:>6.2}%  {:>6}: {}", count, percent(*count), address, text));
+        }
+
+        lines.push(format!("\nmemory traffic per {} cells (reads, writes):", self.region_size));
+        let mut regions: Vec<_> = self.regions.iter().collect();
+        regions.sort_by(|a, b| (b.1.0 + b.1.1).cmp(&(a.1.0 + a.1.1)).then(a.0.cmp(b.0)));
+        for (region, (reads, writes)) in regions.into_iter().take(top) {
+            let start = region * self.region_size;
+            lines.push(format!("{:>12} {:>12}  {}..{}", reads, writes, start, start + self.region_size));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Observer for Profiler {
+    fn step(&mut self, step: &Step) -> Result<(), String> {
+        self.record(step);
+        Ok(())
+    }
+
+    fn needs_input(&mut self) -> Result<Option<i64>, String> {
+        self.input_waits += 1;
+        Ok(None)
+    }
+}
+
+// `profile <program file> [input ...]`
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: profile <program file> [input ...]");
+        return;
+    };
+    let result = load_program(path).and_then(|program| {
+        let inputs = parse_inputs(&args[1..])?;
+        let mut vm = Vm::with_inputs(program, &inputs);
+        let mut profiler = Profiler::default();
+        let status = vm.run_observed(Limits::default(), &mut profiler)?;
+        Ok(format!("{}\n\n{:?}", profiler.report(&vm, 20), status))
+    });
+    match result {
+        Ok(report) => println!("{report}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        // reads a count, then outputs and decrements it until zero
+        let mut vm = Vm::new(vec![3, 100, 4, 100, 1001, 100, -1, 100, 1005, 100, 2, 99]);
+        let mut profiler = Profiler::new(50);
+        assert_eq!(vm.run_observed(Limits::default(), &mut profiler).unwrap(), Status::NeedsInput);
+        vm.push_input(3);
+        assert_eq!(vm.run_observed(Limits::default(), &mut profiler).unwrap(), Status::Halted);
+
+        assert_eq!(profiler.steps, 1 + 3 * 3 + 1);
+        assert_eq!(profiler.input_waits, 1);
+        assert_eq!(profiler.opcodes[&Opcode::Output], 3);
+        assert_eq!(profiler.opcodes[&Opcode::Stop], 1);
+        assert_eq!(profiler.addresses[&8], 3);
+        // cell 100: 3 outputs, 3 sum reads and 3 jump reads; 1 input and 3 sum writes
+        assert_eq!(profiler.regions[&2], (9, 4));
+        // immediate parameters are read out of the code region
+        assert_eq!(profiler.regions[&0], (6, 0));
+
+        let report = profiler.report(&vm, 3);
+        assert!(report.starts_with("11 instructions executed, 1 input waits"));
+        assert!(report.contains("3  27.27%       2: Output [100]"));
+        assert!(report.contains("100..150"));
+    }
+}
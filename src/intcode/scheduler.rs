@@ -0,0 +1,142 @@
+use crate::intcode::vm::{Status, Vm};
+
+// Runs any number of VMs on the current thread. Machines take turns in index order, each
+// running until it halts, blocks on input or uses up its time slice; whatever it printed
+// is then appended to the input queues of the machines it is connected to. The same
+// network and inputs therefore always produce the same interleaving.
+#[derive(Default)]
+pub struct Scheduler {
+    pub machines: Vec<Vm>,
+    routes: Vec<Vec<usize>>,
+    // every value each machine has output, whether or not it was routed anywhere
+    pub outputs: Vec<Vec<i64>>,
+    slice: Option<u64>,
+    pub steps: u64,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // preempt machines after `slice` instructions instead of only when they block
+    pub fn with_slice(mut self, slice: u64) -> Self {
+        self.slice = Some(slice.max(1));
+        self
+    }
+
+    pub fn add(&mut self, vm: Vm) -> usize {
+        self.machines.push(vm);
+        self.routes.push(vec![]);
+        self.outputs.push(vec![]);
+        self.machines.len() - 1
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) -> Result<(), String> {
+        if from >= self.machines.len() || to >= self.machines.len() {
+            return Err(format!("cannot connect {from} -> {to}, only {} machines", self.machines.len()));
+        }
+        self.routes[from].push(to);
+        Ok(())
+    }
+
+    pub fn statuses(&self) -> Vec<Status> {
+        self.machines.iter().map(|vm| vm.status()).collect()
+    }
+
+    // gives machine `id` one turn and returns how many instructions it executed
+    fn turn(&mut self, id: usize) -> Result<u64, String> {
+        let vm = &mut self.machines[id];
+        let mut executed = 0;
+        while self.slice != Some(executed) {
+            if vm.step().map_err(|e| format!("machine {id}: {e}"))?.is_none() {
+                break;
+            }
+            executed += 1;
+        }
+        let produced = vm.take_outputs();
+        for &target in &self.routes[id] {
+            self.machines[target].inputs.extend(&produced);
+        }
+        self.outputs[id].extend(produced);
+        self.steps += executed;
+        Ok(executed)
+    }
+
+    // Round-robins until no machine can make progress: either everything halted, or the
+    // remaining machines are all waiting on input nobody is going to send.
+    pub fn run(&mut self) -> Result<Vec<Status>, String> {
+        loop {
+            let mut progressed = false;
+            for id in 0..self.machines.len() {
+                progressed |= self.turn(id)? > 0;
+            }
+            if !progressed {
+                return Ok(self.statuses());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback_program() -> Vec<i64> {
+        vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26,
+             27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5]
+    }
+
+    #[test]
+    fn test_feedback_ring() {
+        let phases = [9, 8, 7, 6, 5];
+        let mut scheduler = Scheduler::new();
+        for phase in phases {
+            scheduler.add(Vm::with_inputs(feedback_program(), &[phase]));
+        }
+        scheduler.machines[0].push_input(0);
+        for id in 0..5 {
+            scheduler.connect(id, (id + 1) % 5).unwrap();
+        }
+        assert_eq!(scheduler.run().unwrap(), vec![Status::Halted; 5]);
+        assert_eq!(scheduler.outputs[4].last(), Some(&139629729));
+        // the last amplifier's final signal is left unread in the first one's queue
+        assert_eq!(scheduler.machines[0].inputs.back(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_many_machines_deterministic() {
+        // each machine adds one to whatever it reads, forever
+        let increment = vec![3, 11, 101, 1, 11, 11, 4, 11, 1105, 1, 0, 0];
+        let build = |slice: Option<u64>| {
+            let mut scheduler = Scheduler::new();
+            if let Some(slice) = slice {
+                scheduler = scheduler.with_slice(slice);
+            }
+            for _ in 0..300 {
+                scheduler.add(Vm::new(increment.clone()));
+            }
+            for id in 0..299 {
+                scheduler.connect(id, id + 1).unwrap();
+            }
+            scheduler.machines[0].inputs.extend([0, 100, 200]);
+            scheduler
+        };
+        let mut first = build(None);
+        let statuses = first.run().unwrap();
+        assert!(statuses.iter().all(|status| *status == Status::NeedsInput));
+        assert_eq!(first.outputs[299], vec![300, 400, 500]);
+
+        let mut sliced = build(Some(3));
+        sliced.run().unwrap();
+        assert_eq!(sliced.outputs, first.outputs);
+        assert_eq!(sliced.steps, first.steps);
+    }
+
+    #[test]
+    fn test_connect_out_of_range() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(Vm::new(vec![99]));
+        assert!(scheduler.connect(0, 1).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::ops::RangeInclusive;
+use std::thread;
+
+use crate::intcode::compiled::CompiledVm;
+use crate::intcode::vm::Vm;
+
+// Tries every combination of values for a set of patched addresses and collects the ones
+// whose finished VM satisfies a predicate. Candidates are numbered like the digits of a
+// counter, the last patch changing fastest, and split between threads in contiguous chunks.
+//
+//   Search::new(program)
+//       .patch(1, 0..=99)
+//       .patch(2, 0..=99)
+//       .find(|vm| vm.read(0) == 19690720)
+//
+// A candidate that fails with an error, or is still running after `max_steps`
+// instructions, simply doesn't match.
+pub struct Search {
+    program: Vec<i64>,
+    patches: Vec<(usize, RangeInclusive<i64>)>,
+    inputs: Vec<i64>,
+    threads: usize,
+    max_steps: u64,
+}
+
+const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+
+// how many values a range holds, without overflowing on ranges wider than i64
+fn count(values: &RangeInclusive<i64>) -> Option<u64> {
+    u64::try_from((*values.end() as i128 - *values.start() as i128 + 1).max(0)).ok()
+}
+
+impl Search {
+    pub fn new(program: Vec<i64>) -> Self {
+        let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        Self { program, patches: vec![], inputs: vec![], threads, max_steps: DEFAULT_MAX_STEPS }
+    }
+
+    pub fn patch(mut self, address: usize, values: RangeInclusive<i64>) -> Self {
+        self.patches.push((address, values));
+        self
+    }
+
+    pub fn inputs(mut self, inputs: &[i64]) -> Self {
+        self.inputs = inputs.to_vec();
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    fn candidates(&self) -> Result<u64, String> {
+        self.patches.iter().try_fold(1u64, |total, (address, values)| {
+            count(values).and_then(|count| total.checked_mul(count)).ok_or_else(|| format!("too many candidates at address {address}"))
+        })
+    }
+
+    fn values(&self, mut index: u64) -> Vec<i64> {
+        let mut values = vec![0; self.patches.len()];
+        for (value, (_, range)) in values.iter_mut().zip(&self.patches).rev() {
+            // only called with indices below `candidates()`, so every count fits
+            let count = count(range).unwrap_or(u64::MAX);
+            *value = (*range.start() as i128 + (index % count) as i128) as i64;
+            index /= count;
+        }
+        values
+    }
+
+    fn run(&self, values: &[i64]) -> Option<Vm> {
+        let mut program = self.program.clone();
+        for ((address, _), value) in self.patches.iter().zip(values) {
+            *program.get_mut(*address)? = *value;
+        }
+        let mut compiled = CompiledVm::new(Vm::with_inputs(program, &self.inputs));
+        compiled.run_with_limit(Some(self.max_steps)).ok()?;
+        Some(compiled.vm)
+    }
+
+    // every matching combination of patched values, in the order the patches were added
+    pub fn find(&self, predicate: impl Fn(&Vm) -> bool + Sync) -> Result<Vec<Vec<i64>>, String> {
+        if let Some((address, _)) = self.patches.iter().find(|(address, _)| *address >= self.program.len()) {
+            return Err(format!("patch address {address} is outside the program"));
+        }
+        let total = self.candidates()?;
+        let chunk = total.div_ceil(self.threads as u64).max(1);
+        let predicate = &predicate;
+        let chunks: Vec<Vec<Vec<i64>>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..total).step_by(chunk as usize).map(|start| {
+                scope.spawn(move || {
+                    (start..(start + chunk).min(total))
+                        .map(|index| self.values(index))
+                        .filter(|values| self.run(values).is_some_and(|vm| predicate(&vm)))
+                        .collect()
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        Ok(chunks.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program;
+
+    #[test]
+    fn test_day2_target() {
+        let program = load_program("./inputs/day2").unwrap();
+        let matches = Search::new(program.clone()).patch(1, 0..=99).patch(2, 0..=99).find(|vm| vm.read(0) == 19690720).unwrap();
+        assert_eq!(matches.len(), 1);
+        let mut vm = Vm::new(program);
+        vm.memory[1] = matches[0][0];
+        vm.memory[2] = matches[0][1];
+        vm.run().unwrap();
+        assert_eq!(vm.read(0), 19690720);
+    }
+
+    #[test]
+    fn test_all_matches_on_outputs() {
+        // outputs the sum of the cells at 13 and 14, plus its input
+        let search = Search::new(vec![1, 13, 14, 15, 3, 16, 1, 15, 16, 15, 4, 15, 99, 0, 0, 0, 0])
+            .patch(13, 0..=5)
+            .patch(14, -2..=2)
+            .inputs(&[100])
+            .threads(3);
+        let matches = search.find(|vm| vm.outputs == vec![104]).unwrap();
+        assert_eq!(matches, vec![vec![2, 2], vec![3, 1], vec![4, 0], vec![5, -1]]);
+        assert_eq!(search.threads(1).find(|vm| vm.outputs == vec![104]).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_errors_and_empty_ranges() {
+        // a patched opcode that doesn't exist is just not a match
+        let matches = Search::new(vec![1, 0, 0, 0, 99]).patch(0, 1..=3).find(|vm| vm.halted).unwrap();
+        assert_eq!(matches, vec![vec![1], vec![2]]);
+        assert!(Search::new(vec![99]).patch(1, 0..=1).find(|_| true).is_err());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = Search::new(vec![99]).patch(0, 5..=4).find(|_| true).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_step_limit() {
+        // jumps back to the start forever unless cell 1 is patched to 0
+        let search = Search::new(vec![1105, 1, 0, 1105, 0, 1, 99]).patch(1, 0..=1).max_steps(1000);
+        assert_eq!(search.find(|vm| vm.halted).unwrap(), vec![vec![0]]);
+    }
+
+    #[test]
+    fn test_wide_ranges() {
+        let search = Search::new(vec![99, 0, 0]).patch(1, i64::MIN..=i64::MIN + 2).patch(2, i64::MAX - 1..=i64::MAX);
+        let matches = search.find(|vm| vm.read(1) == i64::MIN + 2).unwrap();
+        assert_eq!(matches, vec![vec![i64::MIN + 2, i64::MAX - 1], vec![i64::MIN + 2, i64::MAX]]);
+        assert!(Search::new(vec![99, 0]).patch(1, i64::MIN..=i64::MAX).find(|_| true).unwrap_err().starts_with("too many candidates"));
+    }
+}
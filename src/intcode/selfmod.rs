@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::intcode::disasm::disassemble_instruction;
+use crate::intcode::vm::{Limits, Observer, Status, Step, Vm};
+use crate::intcode::{load_program, parse_inputs, ParamMode};
+
+// An instruction that wrote into code: either over cells that had already been decoded
+// as part of an instruction, or into cells that were decoded afterwards.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Site {
+    pub writer: usize,
+    pub targets: BTreeSet<usize>,
+    pub overwrote: bool,
+    pub generated: bool,
+    // wrote through an immediate mode parameter, i.e. into its own instruction
+    pub immediate: bool,
+}
+
+// Watches a run for writes that touch code. Only cells decoded as part of an executed
+// instruction count as code, so patches to branches that never run aren't reported.
+#[derive(Default)]
+pub struct SelfModTracker {
+    pub writes: u64,
+    pub sites: BTreeMap<usize, Site>,
+    code: HashSet<usize>,
+    // last instruction to write each cell
+    writers: HashMap<usize, usize>,
+}
+
+impl SelfModTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn site(&mut self, writer: usize) -> &mut Site {
+        self.sites.entry(writer).or_insert(Site { writer, ..Site::default() })
+    }
+
+    pub fn record(&mut self, step: &Step) {
+        let instruction = step.instruction;
+        for cell in step.instruction_pointer..step.instruction_pointer + instruction.size() {
+            if let Some(&writer) = self.writers.get(&cell) {
+                let site = self.site(writer);
+                site.targets.insert(cell);
+                site.generated = true;
+            }
+            self.code.insert(cell);
+        }
+        if let Some(write) = step.write {
+            self.writes += 1;
+            self.writers.insert(write.address, step.instruction_pointer);
+            if self.code.contains(&write.address) {
+                let immediate = instruction.opcode.write_param().is_some_and(|index| instruction.modes[index] == ParamMode::Immediate);
+                let site = self.site(step.instruction_pointer);
+                site.targets.insert(write.address);
+                site.overwrote = true;
+                site.immediate |= immediate;
+            }
+        }
+    }
+
+    // cells that were both written and decoded as code at some point in the run
+    pub fn code_written(&self) -> usize {
+        self.writers.keys().filter(|address| self.code.contains(address)).count()
+    }
+
+    // Writing instructions are disassembled from `vm`'s memory, which is the code as it was
+    // at the end of the run.
+    pub fn report(&self, vm: &Vm) -> String {
+        let mut lines = vec![format!("{} writes to {} cells, {} of them code", self.writes, self.writers.len(), self.code_written())];
+        if self.sites.is_empty() {
+            lines.push("no self-modifying code".to_string());
+        }
+        for site in self.sites.values() {
+            let text = disassemble_instruction(&vm.memory, site.writer).map(|(text, _)| text).unwrap_or_default();
+            let targets: Vec<String> = site.targets.iter().map(|target| target.to_string()).collect();
+            let mut notes = vec![];
+            if site.overwrote {
+                notes.push("overwrote code");
+            }
+            if site.generated {
+                notes.push("wrote code that ran later");
+            }
+            if site.immediate {
+                notes.push("immediate destination");
+            }
+            lines.push(format!("{:>6}: {:<32} -> {} ({})", site.writer, text, targets.join(", "), notes.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Observer for SelfModTracker {
+    fn step(&mut self, step: &Step) -> Result<(), String> {
+        self.record(step);
+        Ok(())
+    }
+}
+
+// `selfmod <program file> [input ...]`
+pub fn main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: selfmod <program file> [input ...]");
+        return;
+    };
+    let result = load_program(path).and_then(|program| {
+        let inputs = parse_inputs(&args[1..])?;
+        let mut vm = Vm::with_inputs(program, &inputs);
+        let mut tracker = SelfModTracker::new();
+        let status = vm.run_observed(Limits::default(), &mut tracker)?;
+        Ok(format!("{}\n\n{:?}", tracker.report(&vm), status))
+    });
+    match result {
+        Ok(report) => println!("{report}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_sites() {
+        let program = assemble("
+                Sum 1, 0, [patch+1]
+        patch:  Output 5
+                Sum 7, 0, [0]
+                Sum 2, 2, 0
+                Sum 0, 0, [data]
+                Stop
+        data:   .data 9
+        ").unwrap();
+        let mut vm = Vm::new(program);
+        let mut tracker = SelfModTracker::new();
+        assert_eq!(vm.run_observed(Limits::default(), &mut tracker).unwrap(), Status::Halted);
+        assert_eq!(vm.outputs, vec![1]);
+
+        assert_eq!(tracker.writes, 4);
+        assert_eq!(tracker.code_written(), 3);
+        assert_eq!(tracker.sites.keys().copied().collect::<Vec<_>>(), vec![0, 6, 10]);
+        assert_eq!(tracker.sites[&0], Site { writer: 0, targets: BTreeSet::from([5]), generated: true, ..Site::default() });
+        assert_eq!(tracker.sites[&6], Site { writer: 6, targets: BTreeSet::from([0]), overwrote: true, ..Site::default() });
+        assert_eq!(tracker.sites[&10], Site { writer: 10, targets: BTreeSet::from([13]), overwrote: true, immediate: true, ..Site::default() });
+
+        let report = tracker.report(&vm);
+        assert!(report.starts_with("4 writes to 4 cells, 3 of them code"));
+        assert!(report.contains("    10: Sum 2, 2, 4"));
+        assert!(report.contains("-> 13 (overwrote code, immediate destination)"));
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let mut vm = Vm::with_inputs(vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0], &[21]);
+        let mut tracker = SelfModTracker::new();
+        vm.run_observed(Limits::default(), &mut tracker).unwrap();
+        assert!(tracker.sites.is_empty());
+        assert!(tracker.report(&vm).ends_with("no self-modifying code"));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs;
+
+use crate::intcode::vm::{Overflow, Vm};
+use crate::intcode::{parse_program, to_intcode_string};
+
+const HEADER: &str = "intcode-snapshot 2";
+// written before the overflow policy was saved; these load with the default policy
+const HEADER_V1: &str = "intcode-snapshot 1";
+
+// Plain text so snapshots can be inspected and diffed:
+//
+//   intcode-snapshot 2
+//   ip 23
+//   rb 1008
+//   halted false
+//   overflow Error
+//   inputs 1,0
+//   outputs 42
+//   memory 109,1,204,-1,...
+pub fn encode(vm: &Vm) -> String {
+    let inputs: Vec<i64> = vm.inputs.iter().copied().collect();
+    format!("{HEADER}\nip {}\nrb {}\nhalted {}\noverflow {:?}\ninputs {}\noutputs {}\nmemory {}\n",
+            vm.instruction_pointer, vm.relative_base, vm.halted, vm.overflow,
+            to_intcode_string(&inputs), to_intcode_string(&vm.outputs), to_intcode_string(&vm.memory))
+}
+
+pub fn is_snapshot(text: &str) -> bool {
+    matches!(text.lines().next(), Some(HEADER | HEADER_V1))
+}
+
+pub fn decode(snapshot: &str) -> Result<Vm, String> {
+    let mut lines = snapshot.lines();
+    if !is_snapshot(snapshot) {
+        return Err("not an intcode snapshot".to_string());
+    }
+    let version = lines.next();
+    let mut field = |name: &str| -> Result<String, String> {
+        let line = lines.next().ok_or_else(|| format!("snapshot is missing {name}"))?;
+        match line.split_once(' ') {
+            Some((key, value)) if key == name => Ok(value.to_string()),
+            None if line == name => Ok(String::new()),
+            _ => Err(format!("expected {name} in snapshot, found {line:?}")),
+        }
+    };
+    let instruction_pointer = field("ip")?.parse::<usize>().map_err(|e| format!("ip: {e}"))?;
+    let relative_base = field("rb")?.parse::<i64>().map_err(|e| format!("rb: {e}"))?;
+    let halted = field("halted")?.parse::<bool>().map_err(|e| format!("halted: {e}"))?;
+    let overflow = if version == Some(HEADER_V1) {
+        Overflow::default()
+    } else {
+        let overflow = field("overflow")?;
+        Overflow::from_name(&overflow).ok_or_else(|| format!("overflow: unknown policy {overflow:?}"))?
+    };
+    let inputs = parse_program(&field("inputs")?)?;
+    let outputs = parse_program(&field("outputs")?)?;
+    let memory = parse_program(&field("memory")?)?;
+
+    Ok(Vm { memory, instruction_pointer, relative_base, inputs: inputs.into(), outputs, halted, overflow })
+}
+
+pub fn save(vm: &Vm, path: &str) -> Result<(), String> {
+    fs::write(path, encode(vm)).map_err(|e| format!("{path}: {e}"))
+}
+
+pub fn load(path: &str) -> Result<Vm, String> {
+    let snapshot = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    decode(&snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use crate::intcode::vm::Status;
+
+    fn create_vec() -> Vec<i64> {
+        // echoes every input back doubled, forever
+        vec![3, 100, 1002, 100, 2, 100, 4, 100, 1105, 1, 0]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut vm = Vm::with_inputs(create_vec(), &[3, 4, 5]);
+        vm.step().unwrap();
+        vm.relative_base = -7;
+        let decoded = decode(&encode(&vm)).unwrap();
+        assert_eq!(decoded, vm);
+
+        let empty = Vm::new(vec![99]).with_overflow(Overflow::Saturate);
+        assert_eq!(decode(&encode(&empty)).unwrap(), empty);
+        assert!(decode("memory 1,2,3").is_err());
+        assert!(decode(&encode(&vm).replace("rb -7", "rb x")).is_err());
+    }
+
+    #[test]
+    fn test_version_1() {
+        let snapshot = "intcode-snapshot 1\nip 2\nrb 0\nhalted false\ninputs 5\noutputs \nmemory 3,5,4,5,99,0\n";
+        assert!(is_snapshot(snapshot));
+        let mut vm = decode(snapshot).unwrap();
+        assert_eq!(vm.overflow, Overflow::Error);
+        assert_eq!(vm.inputs, [5]);
+        assert!(decode(&snapshot.replace("intcode-snapshot 1", "intcode-snapshot 2")).is_err());
+    }
+
+    #[test]
+    fn test_resume_from_file() {
+        let path = env::temp_dir().join("intcode_snapshot_test.txt");
+        let path = path.to_str().unwrap();
+        let mut vm = Vm::with_inputs(create_vec(), &[1, 2]);
+        assert_eq!(vm.run().unwrap(), Status::NeedsInput);
+        vm.push_input(10);
+        save(&vm, path).unwrap();
+
+        let mut restored = load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        restored.push_input(20);
+        restored.run().unwrap();
+        assert_eq!(restored.outputs, vec![2, 4, 20, 40]);
+    }
+}
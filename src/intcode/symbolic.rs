@@ -0,0 +1,387 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::intcode::{Instruction, Opcode, ParamMode};
+
+const MAX_STEPS: u64 = 1_000_000;
+
+// A polynomial with integer coefficients over memory cells, each monomial being the sorted
+// list of the addresses multiplied together. `[1]` is the variable at address 1.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expr {
+    terms: BTreeMap<Vec<usize>, i64>,
+}
+
+impl Expr {
+    pub fn constant(value: i64) -> Self {
+        let mut expr = Self::default();
+        if value != 0 {
+            expr.terms.insert(vec![], value);
+        }
+        expr
+    }
+
+    pub fn variable(address: usize) -> Self {
+        Self { terms: BTreeMap::from([(vec![address], 1)]) }
+    }
+
+    // the value, when no variable is involved
+    pub fn value(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((monomial, coefficient)) if monomial.is_empty() && self.terms.len() == 1 => Some(*coefficient),
+            _ => None,
+        }
+    }
+
+    pub fn variables(&self) -> Vec<usize> {
+        let mut variables: Vec<usize> = self.terms.keys().flatten().copied().collect();
+        variables.sort();
+        variables.dedup();
+        variables
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.terms.keys().all(|monomial| monomial.len() <= 1)
+    }
+
+    pub fn coefficient(&self, monomial: &[usize]) -> i64 {
+        self.terms.get(monomial).copied().unwrap_or(0)
+    }
+
+    fn add_term(&mut self, monomial: Vec<usize>, coefficient: i64) -> Option<()> {
+        let entry = self.terms.entry(monomial).or_insert(0);
+        *entry = entry.checked_add(coefficient)?;
+        if *entry == 0 {
+            self.terms.retain(|_, coefficient| *coefficient != 0);
+        }
+        Some(())
+    }
+
+    pub fn checked_add(&self, other: &Expr) -> Option<Expr> {
+        let mut sum = self.clone();
+        for (monomial, coefficient) in &other.terms {
+            sum.add_term(monomial.clone(), *coefficient)?;
+        }
+        Some(sum)
+    }
+
+    pub fn checked_mul(&self, other: &Expr) -> Option<Expr> {
+        let mut product = Expr::default();
+        for (left, a) in &self.terms {
+            for (right, b) in &other.terms {
+                let mut monomial = [&left[..], &right[..]].concat();
+                monomial.sort();
+                product.add_term(monomial, a.checked_mul(*b)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    pub fn checked_sub(&self, other: &Expr) -> Option<Expr> {
+        self.checked_add(&other.checked_mul(&Expr::constant(-1))?)
+    }
+
+    // Every assignment of the variables within `ranges` that makes the expression equal
+    // `target`, listed in the order of `ranges`. Only linear expressions can be solved: the
+    // last variable with a non-zero coefficient is computed from the others instead of tried.
+    pub fn solve(&self, target: i64, ranges: &[(usize, RangeInclusive<i64>)]) -> Result<Vec<Vec<i64>>, String> {
+        if !self.is_linear() {
+            return Err(format!("{self} is not linear"));
+        }
+        if let Some(variable) = self.variables().into_iter().find(|variable| ranges.iter().all(|(address, _)| address != variable)) {
+            return Err(format!("no range given for [{variable}]"));
+        }
+        let coefficients: Vec<i128> = ranges.iter().map(|(address, _)| self.coefficient(&[*address]) as i128).collect();
+        let pivot = coefficients.iter().rposition(|coefficient| *coefficient != 0);
+        let remainder = target as i128 - self.coefficient(&[]) as i128;
+
+        let mut solutions = vec![];
+        let mut values: Vec<i64> = ranges.iter().map(|(_, range)| *range.start()).collect();
+        if ranges.iter().any(|(_, range)| range.is_empty()) {
+            return Ok(solutions);
+        }
+        loop {
+            let sum: i128 = values.iter().zip(&coefficients).enumerate()
+                .filter(|(index, _)| Some(*index) != pivot)
+                .map(|(_, (value, coefficient))| *value as i128 * coefficient)
+                .sum();
+            match pivot {
+                Some(pivot) => {
+                    let rest = remainder - sum;
+                    if rest % coefficients[pivot] == 0 {
+                        if let Ok(value) = i64::try_from(rest / coefficients[pivot]) {
+                            if ranges[pivot].1.contains(&value) {
+                                values[pivot] = value;
+                                solutions.push(values.clone());
+                            }
+                        }
+                    }
+                }
+                None if remainder == 0 => solutions.push(values.clone()),
+                None => {}
+            }
+            // count up like an odometer, skipping the pivot
+            let next = (0..ranges.len()).rev().filter(|index| Some(*index) != pivot).find(|index| values[*index] < *ranges[*index].1.end());
+            let Some(index) = next else { break };
+            values[index] += 1;
+            for (later, (_, range)) in ranges.iter().enumerate().skip(index + 1) {
+                values[later] = *range.start();
+            }
+        }
+        solutions.sort();
+        Ok(solutions)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        let mut terms: Vec<_> = self.terms.iter().collect();
+        terms.sort_by_key(|(monomial, _)| std::cmp::Reverse(monomial.len()));
+        for (index, (monomial, coefficient)) in terms.into_iter().enumerate() {
+            let sign = match (index, *coefficient < 0) {
+                (0, true) => "-",
+                (0, false) => "",
+                (_, true) => " - ",
+                (_, false) => " + ",
+            };
+            let mut factors: Vec<String> = monomial.iter().map(|address| format!("[{address}]")).collect();
+            if coefficient.unsigned_abs() != 1 || monomial.is_empty() {
+                factors.insert(0, coefficient.unsigned_abs().to_string());
+            }
+            write!(f, "{}{}", sign, factors.join("*"))?;
+        }
+        Ok(())
+    }
+}
+
+// A cell's contents, or why they can't be tracked: reading through an address that depends
+// on a variable could have fetched any cell. That only matters if the value is used later.
+pub type Value = Result<Expr, String>;
+
+// What a program leaves behind when some of its cells are unknowns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Evaluation {
+    pub memory: Vec<Value>,
+    pub outputs: Vec<Expr>,
+    pub steps: u64,
+}
+
+impl Evaluation {
+    pub fn read(&self, address: usize) -> Result<Expr, String> {
+        self.memory.get(address).cloned().unwrap_or(Ok(Expr::default())).map_err(|reason| format!("[{address}] {reason}"))
+    }
+}
+
+struct Evaluator {
+    memory: Vec<Value>,
+    ip: usize,
+    relative_base: i64,
+}
+
+impl Evaluator {
+    fn read(&self, address: usize) -> Value {
+        self.memory.get(address).cloned().unwrap_or(Ok(Expr::default()))
+    }
+
+    fn concrete(&self, value: Value, what: &str) -> Result<i64, String> {
+        let expr = value.map_err(|reason| format!("{what} a value that {reason}, at ip {}", self.ip))?;
+        expr.value().ok_or_else(|| format!("{what} {expr} at ip {}", self.ip))
+    }
+
+    fn address(&self, instruction: Instruction, index: usize) -> Result<Result<usize, Expr>, String> {
+        let cell = self.ip + 1 + index;
+        let offset = match instruction.modes[index] {
+            ParamMode::Positional => 0,
+            ParamMode::Immediate => return Ok(Ok(cell)),
+            ParamMode::Relative => self.relative_base,
+        };
+        let expr = self.read(cell).map_err(|reason| format!("address from a value that {reason}, at ip {}", self.ip))?;
+        let Some(value) = expr.value() else { return Ok(Err(expr)) };
+        let address = offset.checked_add(value).ok_or_else(|| format!("relative address overflowed at ip {}", self.ip))?;
+        usize::try_from(address).map(Ok).map_err(|_| format!("negative address {address} at ip {}", self.ip))
+    }
+
+    fn param(&self, instruction: Instruction, index: usize) -> Result<Value, String> {
+        Ok(match self.address(instruction, index)? {
+            Ok(address) => self.read(address),
+            Err(expr) => Err(format!("was read through the symbolic address {expr} at ip {}", self.ip)),
+        })
+    }
+
+    fn write(&mut self, instruction: Instruction, index: usize, value: Value) -> Result<(), String> {
+        let address = self.address(instruction, index)?.map_err(|expr| format!("write to symbolic address {expr} at ip {}", self.ip))?;
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Ok(Expr::default()));
+        }
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    fn compare(&self, param1: Value, param2: Value, opcode: Opcode) -> Result<Value, String> {
+        let (Ok(param1), Ok(param2)) = (&param1, &param2) else {
+            return Ok(param1.and(param2));
+        };
+        // comparable when the difference doesn't depend on the variables
+        let difference = param1.checked_sub(param2).ok_or_else(|| format!("{:?} of {} and {} overflowed at ip {}", opcode, param1, param2, self.ip))?;
+        let difference = self.concrete(Ok(difference), "comparison of")?;
+        let result = if opcode == Opcode::LessThan { difference < 0 } else { difference == 0 };
+        Ok(Ok(Expr::constant(result as i64)))
+    }
+}
+
+// Runs `program` with the cells at `variables` left unknown, tracking every other cell as an
+// expression over them. Control flow has to be decidable without knowing the variables:
+// branching on, jumping to, decoding, writing through or outputting anything that still
+// depends on a variable is an error, as is running out of `inputs`.
+pub fn evaluate(program: &[i64], variables: &[usize], inputs: &[i64]) -> Result<Evaluation, String> {
+    let mut memory: Vec<Value> = program.iter().map(|value| Ok(Expr::constant(*value))).collect();
+    for variable in variables {
+        if *variable >= memory.len() {
+            memory.resize(variable + 1, Ok(Expr::default()));
+        }
+        memory[*variable] = Ok(Expr::variable(*variable));
+    }
+    let mut evaluator = Evaluator { memory, ip: 0, relative_base: 0 };
+    let mut inputs: VecDeque<i64> = inputs.iter().copied().collect();
+    let mut outputs = vec![];
+
+    for steps in 0..MAX_STEPS {
+        let ip = evaluator.ip;
+        let value = evaluator.concrete(evaluator.read(ip), "decoding")?;
+        let instruction = Instruction::decode(value).map_err(|e| format!("{e} at ip {ip}"))?;
+        let mut next_ip = ip + instruction.size();
+
+        match instruction.opcode {
+            Opcode::Sum | Opcode::Multiply => {
+                let param1 = evaluator.param(instruction, 0)?;
+                let param2 = evaluator.param(instruction, 1)?;
+                let value = match (&param1, &param2) {
+                    (Ok(param1), Ok(param2)) => {
+                        let value = if instruction.opcode == Opcode::Sum { param1.checked_add(param2) } else { param1.checked_mul(param2) };
+                        Ok(value.ok_or_else(|| format!("{:?} of {} and {} overflowed at ip {}", instruction.opcode, param1, param2, ip))?)
+                    }
+                    _ => param1.and(param2),
+                };
+                evaluator.write(instruction, 2, value)?;
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let param1 = evaluator.param(instruction, 0)?;
+                let param2 = evaluator.param(instruction, 1)?;
+                let value = evaluator.compare(param1, param2, instruction.opcode)?;
+                evaluator.write(instruction, 2, value)?;
+            }
+            Opcode::Input => {
+                let value = inputs.pop_front().ok_or_else(|| format!("ran out of input at ip {ip}"))?;
+                evaluator.write(instruction, 0, Ok(Expr::constant(value)))?;
+            }
+            Opcode::Output => {
+                let value = evaluator.param(instruction, 0)?;
+                outputs.push(value.map_err(|reason| format!("output of a value that {reason}, at ip {ip}"))?);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = evaluator.concrete(evaluator.param(instruction, 0)?, "branch on")?;
+                if (condition != 0) == (instruction.opcode == Opcode::JumpIfTrue) {
+                    let target = evaluator.concrete(evaluator.param(instruction, 1)?, "jump to")?;
+                    next_ip = usize::try_from(target).map_err(|_| format!("jump to negative address {target} at ip {ip}"))?;
+                }
+            }
+            Opcode::RelativeBaseOffset => {
+                let offset = evaluator.concrete(evaluator.param(instruction, 0)?, "relative base offset of")?;
+                evaluator.relative_base = evaluator.relative_base.checked_add(offset).ok_or_else(|| format!("relative base overflowed at ip {ip}"))?;
+            }
+            Opcode::Stop => return Ok(Evaluation { memory: evaluator.memory, outputs, steps: steps + 1 }),
+        }
+        evaluator.ip = next_ip;
+    }
+    Err(format!("no halt within {MAX_STEPS} steps"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+    use crate::intcode::load_program;
+    use crate::intcode::vm::Vm;
+
+    #[test]
+    fn test_expr() {
+        let x = Expr::variable(1);
+        let y = Expr::variable(2);
+        let expr = x.checked_mul(&Expr::constant(3)).unwrap().checked_add(&y).unwrap().checked_sub(&Expr::constant(4)).unwrap();
+        assert_eq!(expr.to_string(), "3*[1] + [2] - 4");
+        assert!(expr.is_linear());
+        assert_eq!(expr.variables(), vec![1, 2]);
+        let square = expr.checked_mul(&x).unwrap();
+        assert_eq!(square.to_string(), "3*[1]*[1] + [1]*[2] - 4*[1]");
+        assert!(!square.is_linear());
+        assert_eq!(expr.checked_sub(&expr).unwrap().value(), Some(0));
+        assert!(Expr::constant(i64::MAX).checked_add(&Expr::constant(1)).is_none());
+    }
+
+    #[test]
+    fn test_day2() {
+        let program = load_program("./inputs/day2").unwrap();
+        let evaluation = evaluate(&program, &[1, 2], &[]).unwrap();
+        let expr = evaluation.read(0).unwrap();
+        assert!(expr.is_linear());
+        assert_eq!(expr.coefficient(&[2]), 1);
+
+        let solutions = expr.solve(19690720, &[(1, 0..=99), (2, 0..=99)]).unwrap();
+        assert_eq!(solutions.len(), 1);
+        let mut vm = Vm::new(program);
+        vm.memory[1] = solutions[0][0];
+        vm.memory[2] = solutions[0][1];
+        vm.run().unwrap();
+        assert_eq!(vm.read(0), 19690720);
+    }
+
+    #[test]
+    fn test_solve() {
+        let expr = Expr::variable(5).checked_mul(&Expr::constant(2)).unwrap().checked_add(&Expr::variable(6)).unwrap();
+        assert_eq!(expr.solve(5, &[(5, 0..=9), (6, 0..=2)]).unwrap(), vec![vec![2, 1]]);
+        assert_eq!(expr.solve(6, &[(5, 0..=9), (6, -2..=2)]).unwrap(), vec![vec![2, 2], vec![3, 0], vec![4, -2]]);
+        // a variable that doesn't matter takes every value in its range
+        assert_eq!(expr.solve(4, &[(5, 2..=2), (6, 0..=0), (7, 1..=3)]).unwrap(), vec![vec![2, 0, 1], vec![2, 0, 2], vec![2, 0, 3]]);
+        assert!(expr.solve(4, &[(5, 0..=9)]).is_err());
+        assert!(expr.checked_mul(&expr).unwrap().solve(4, &[(5, 0..=9), (6, 0..=9)]).is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let program = assemble("
+                Input [count]
+                Multiply [x], [count], [y]
+                Sum [y], 7, [y]
+                Equals [x], [x], [same]
+                JumpIfFalse [same], end
+                Output [y]
+        end:    Stop
+        x:      .data 0
+        y:      .data 0
+        count:  .data 0
+        same:   .data 0
+        ").unwrap();
+        let x = program.len() - 4;
+        let evaluation = evaluate(&program, &[x], &[3]).unwrap();
+        assert_eq!(evaluation.outputs, vec![evaluation.read(x + 1).unwrap()]);
+        assert_eq!(evaluation.outputs[0].to_string(), format!("3*[{x}] + 7"));
+        assert_eq!(evaluation.steps, 7);
+
+        assert_eq!(evaluate(&program, &[x], &[]).unwrap_err(), "ran out of input at ip 0");
+        let branchy = assemble("JumpIfTrue [x], 0\nStop\nx: .data 0").unwrap();
+        assert_eq!(evaluate(&branchy, &[4], &[]).unwrap_err(), "branch on [4] at ip 0");
+        assert_eq!(evaluate(&[1, 5, 5, 4, 99, 0], &[5], &[]).unwrap_err(), "decoding 2*[5] at ip 4");
+        assert_eq!(evaluate(&[1, 0, 0, 3, 99], &[3], &[]).unwrap_err(), "write to symbolic address [3] at ip 0");
+
+        // a cell read through a variable is only a problem when it's used
+        let evaluation = evaluate(&[1, 1, 0, 9, 1101, 0, 0, 9, 99, 0], &[1], &[]).unwrap();
+        assert_eq!(evaluation.read(9).unwrap(), Expr::default());
+        let evaluation = evaluate(&[1, 1, 0, 5, 99, 0], &[1], &[]).unwrap();
+        assert_eq!(evaluation.read(5).unwrap_err(), "[5] was read through the symbolic address [1] at ip 0");
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::intcode::scheduler::Scheduler;
+use crate::intcode::vm::{Status, Vm};
+
+struct MachineSpec {
+    name: String,
+    program: Vec<i64>,
+    inputs: Vec<i64>,
+}
+
+// Describes a network of named machines and how their outputs feed each other's inputs.
+// Every machine gets its own initial inputs queued before anything is routed to it, so
+// e.g. amplifier phase settings are always read first.
+//
+//   Topology::new()
+//       .machine("A", program.clone(), &[9, 0])
+//       .machine("B", program.clone(), &[8])
+//       .ring(&["A", "B"])
+//       .run()
+#[derive(Default)]
+pub struct Topology {
+    machines: Vec<MachineSpec>,
+    edges: Vec<(String, String)>,
+    slice: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MachineReport {
+    pub name: String,
+    pub outputs: Vec<i64>,
+    pub status: Status,
+    pub vm: Vm,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub machines: Vec<MachineReport>,
+    pub steps: u64,
+}
+
+impl Report {
+    pub fn get(&self, name: &str) -> Option<&MachineReport> {
+        self.machines.iter().find(|machine| machine.name == name)
+    }
+
+    pub fn all_halted(&self) -> bool {
+        self.machines.iter().all(|machine| machine.status == Status::Halted)
+    }
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn machine(mut self, name: &str, program: Vec<i64>, inputs: &[i64]) -> Self {
+        self.machines.push(MachineSpec { name: name.to_string(), program, inputs: inputs.to_vec() });
+        self
+    }
+
+    pub fn edge(mut self, from: &str, to: &str) -> Self {
+        self.edges.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    // a -> b -> c
+    pub fn pipeline(mut self, names: &[&str]) -> Self {
+        for pair in names.windows(2) {
+            self = self.edge(pair[0], pair[1]);
+        }
+        self
+    }
+
+    // a -> b -> c -> a
+    pub fn ring(self, names: &[&str]) -> Self {
+        match (names.first(), names.last()) {
+            (Some(first), Some(last)) if names.len() > 1 => self.pipeline(names).edge(last, first),
+            _ => self,
+        }
+    }
+
+    // every output of `from` is copied to each of `to`
+    pub fn fan_out(mut self, from: &str, to: &[&str]) -> Self {
+        for name in to {
+            self = self.edge(from, name);
+        }
+        self
+    }
+
+    // outputs of all of `from` are merged into `to`, in scheduling order
+    pub fn fan_in(mut self, from: &[&str], to: &str) -> Self {
+        for name in from {
+            self = self.edge(name, to);
+        }
+        self
+    }
+
+    pub fn with_slice(mut self, slice: u64) -> Self {
+        self.slice = Some(slice);
+        self
+    }
+
+    pub fn run(self) -> Result<Report, String> {
+        let mut scheduler = match self.slice {
+            Some(slice) => Scheduler::new().with_slice(slice),
+            None => Scheduler::new(),
+        };
+        let mut ids = HashMap::new();
+        let mut names = vec![];
+        for spec in self.machines {
+            let id = scheduler.add(Vm::with_inputs(spec.program, &spec.inputs));
+            if ids.insert(spec.name.clone(), id).is_some() {
+                return Err(format!("machine {} is defined twice", spec.name));
+            }
+            names.push(spec.name);
+        }
+        for (from, to) in &self.edges {
+            let lookup = |name: &String| ids.get(name).copied().ok_or_else(|| format!("edge {from} -> {to} uses unknown machine {name}"));
+            scheduler.connect(lookup(from)?, lookup(to)?)?;
+        }
+
+        let statuses = scheduler.run()?;
+        let machines = names.into_iter()
+            .zip(statuses)
+            .zip(scheduler.machines.into_iter().zip(scheduler.outputs))
+            .map(|((name, status), (vm, outputs))| MachineReport { name, outputs, status, vm })
+            .collect();
+        Ok(Report { machines, steps: scheduler.steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_vec() -> Vec<i64> {
+        // outputs each input multiplied by the first one it read
+        vec![3, 14, 3, 15, 2, 14, 15, 16, 4, 16, 1105, 1, 2, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_amplifier_ring() {
+        let program = vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26,
+                           27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5];
+        let report = Topology::new()
+            .machine("A", program.clone(), &[9, 0])
+            .machine("B", program.clone(), &[8])
+            .machine("C", program.clone(), &[7])
+            .machine("D", program.clone(), &[6])
+            .machine("E", program, &[5])
+            .ring(&["A", "B", "C", "D", "E"])
+            .run()
+            .unwrap();
+        assert!(report.all_halted());
+        assert_eq!(report.get("E").unwrap().outputs.last(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_fan_out_and_in() {
+        // source doubles, then both branches scale by 3 and 5, merged into a sink that adds 1
+        let report = Topology::new()
+            .machine("source", create_vec(), &[2, 1, 10])
+            .machine("left", create_vec(), &[3])
+            .machine("right", create_vec(), &[5])
+            .machine("sink", create_vec(), &[1])
+            .fan_out("source", &["left", "right"])
+            .fan_in(&["left", "right"], "sink")
+            .run()
+            .unwrap();
+        assert_eq!(report.get("source").unwrap().outputs, vec![2, 20]);
+        assert_eq!(report.get("left").unwrap().outputs, vec![6, 60]);
+        assert_eq!(report.get("right").unwrap().outputs, vec![10, 100]);
+        assert_eq!(report.get("sink").unwrap().outputs, vec![6, 60, 10, 100]);
+        assert_eq!(report.get("sink").unwrap().status, Status::NeedsInput);
+    }
+
+    #[test]
+    fn test_pipeline_and_errors() {
+        let report = Topology::new()
+            .machine("a", create_vec(), &[2, 1, 2])
+            .machine("b", create_vec(), &[10])
+            .pipeline(&["a", "b"])
+            .run()
+            .unwrap();
+        assert_eq!(report.get("b").unwrap().outputs, vec![20, 40]);
+        assert_eq!(report.get("b").unwrap().vm.read(16), 40);
+
+        assert!(Topology::new().machine("a", vec![99], &[]).edge("a", "b").run().is_err());
+        assert!(Topology::new().machine("a", vec![99], &[]).machine("a", vec![99], &[]).run().is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write as _};
+use std::ops::Range;
+
+use crate::intcode::vm::{Limits, Observer, Status, Step, Vm, Write};
+use crate::intcode::{load_program, parse_inputs, parse_range, Instruction};
+
+// One line per executed instruction:
+//
+//   <step> <ip> <instruction> <operands|-> [r=<addr>:<addr>] [w=<addr>:<old>:<new>] [rb=<old>:<new>] [in=<v>] [out=<v>]
+//
+// e.g. `17 6 1005 1,0` or `18 0 4 42 out=42`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub number: u64,
+    pub step: Step,
+}
+
+impl TraceEntry {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let error = || format!("malformed trace line {line:?}");
+        let mut fields = line.split_whitespace();
+        let mut next_number = || fields.next().and_then(|field| field.parse::<i64>().ok()).ok_or_else(error);
+        let number = next_number()? as u64;
+        let instruction_pointer = next_number()? as usize;
+        let instruction = Instruction::decode(next_number()?)?;
+
+        let mut step = Step { instruction_pointer, instruction, operands: [0; 3], reads: [None; 2], write: None, relative_base: None, input: None, output: None };
+        let operands = fields.next().ok_or_else(error)?;
+        if operands != "-" {
+            for (index, operand) in operands.split(',').enumerate().take(3) {
+                step.operands[index] = operand.parse().map_err(|_| error())?;
+            }
+        }
+        for field in fields {
+            let (key, value) = field.split_once('=').ok_or_else(error)?;
+            let values = value.split(':').map(|value| value.parse::<i64>().map_err(|_| error())).collect::<Result<Vec<_>, _>>()?;
+            match (key, values.as_slice()) {
+                ("r", [address]) => step.reads[0] = Some(*address as usize),
+                ("r", [first, second]) => step.reads = [Some(*first as usize), Some(*second as usize)],
+                ("w", [address, old, new]) => step.write = Some(Write { address: *address as usize, old: *old, new: *new }),
+                ("rb", [old, new]) => step.relative_base = Some((*old, *new)),
+                ("in", [value]) => step.input = Some(*value),
+                ("out", [value]) => step.output = Some(*value),
+                _ => return Err(error()),
+            }
+        }
+        Ok(Self { number, step })
+    }
+
+    // an entry matches an address range if it executed there or wrote to a cell in it
+    pub fn touches(&self, addresses: &Range<usize>) -> bool {
+        addresses.contains(&self.step.instruction_pointer)
+            || self.step.write.is_some_and(|write| addresses.contains(&write.address))
+    }
+
+    // compact form written to trace files
+    pub fn encode(&self) -> String {
+        let step = &self.step;
+        let arity = step.instruction.opcode.arity();
+        let operands = if arity == 0 {
+            "-".to_string()
+        } else {
+            step.operands[..arity].iter().map(|operand| operand.to_string()).collect::<Vec<_>>().join(",")
+        };
+        let mut line = format!("{} {} {} {}", self.number, step.instruction_pointer, step.instruction.encode(), operands);
+        let reads: Vec<String> = step.reads.iter().flatten().map(|address| address.to_string()).collect();
+        if !reads.is_empty() {
+            line += &format!(" r={}", reads.join(":"));
+        }
+        if let Some(write) = step.write {
+            line += &format!(" w={}:{}:{}", write.address, write.old, write.new);
+        }
+        if let Some((old, new)) = step.relative_base {
+            line += &format!(" rb={old}:{new}");
+        }
+        if let Some(value) = step.input {
+            line += &format!(" in={value}");
+        }
+        if let Some(value) = step.output {
+            line += &format!(" out={value}");
+        }
+        line
+    }
+}
+
+// human readable form used when printing a filtered trace
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = &self.step;
+        let opcode = step.instruction.opcode;
+        let reads: Vec<String> = (0..opcode.arity())
+            .filter(|index| opcode.write_param() != Some(*index))
+            .map(|index| step.operands[index].to_string())
+            .collect();
+        write!(f, "{:>8} {:>6}: {:?}", self.number, step.instruction_pointer, opcode)?;
+        if !reads.is_empty() {
+            write!(f, " {}", reads.join(", "))?;
+        }
+        if let Some(write) = step.write {
+            write!(f, " -> [{}] {} => {}", write.address, write.old, write.new)?;
+        }
+        if let Some((old, new)) = step.relative_base {
+            write!(f, " rb {old} => {new}")?;
+        }
+        if let Some(value) = step.input {
+            write!(f, " in {value}")?;
+        }
+        if let Some(value) = step.output {
+            write!(f, " out {value}")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Tracer {
+    writer: BufWriter<File>,
+    steps: u64,
+}
+
+impl Tracer {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self { writer: BufWriter::new(file), steps: 0 })
+    }
+
+    pub fn record(&mut self, step: Step) -> Result<(), String> {
+        let entry = TraceEntry { number: self.steps, step };
+        self.steps += 1;
+        writeln!(self.writer, "{}", entry.encode()).map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<u64, String> {
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.steps)
+    }
+}
+
+impl Observer for Tracer {
+    fn step(&mut self, step: &Step) -> Result<(), String> {
+        self.record(*step)
+    }
+}
+
+pub fn filter_trace(path: &str, steps: Option<Range<u64>>, addresses: Option<Range<usize>>) -> Result<Vec<TraceEntry>, String> {
+    let input = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut entries = vec![];
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let entry = TraceEntry::parse(line)?;
+        if steps.as_ref().is_some_and(|steps| !steps.contains(&entry.number)) {
+            continue;
+        }
+        if addresses.as_ref().is_some_and(|addresses| !entry.touches(addresses)) {
+            continue;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// `trace <program file> <trace file> [input ...]`
+pub fn main(args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("usage: trace <program file> <trace file> [input ...]");
+        return;
+    }
+    let result = load_program(&args[0]).and_then(|program| {
+        let inputs = parse_inputs(&args[2..])?;
+        let mut vm = Vm::with_inputs(program, &inputs);
+        let mut tracer = Tracer::create(&args[1])?;
+        let status = vm.run_observed(Limits::default(), &mut tracer)?;
+        Ok((status, tracer.finish()?, vm.outputs))
+    });
+    match result {
+        Ok((status, steps, outputs)) => println!("{status:?} after {steps} steps, outputs: {outputs:?}"),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+// `trace-filter <trace file> [--steps a..b] [--addresses a..b]`
+pub fn filter_main(args: &[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: trace-filter <trace file> [--steps a..b] [--addresses a..b]");
+        return;
+    };
+    let mut steps = None;
+    let mut addresses = None;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(|value| value.as_str()).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--steps" => parse_range(value).map(|range| steps = Some(range.start as u64..range.end as u64)),
+            "--addresses" => parse_range(value).map(|range| addresses = Some(range)),
+            _ => Err(format!("unknown option {option}")),
+        };
+        if let Err(e) = parsed {
+            eprintln!("{e}");
+            return;
+        }
+    }
+    match filter_trace(path, steps, addresses) {
+        Ok(entries) => entries.iter().for_each(|entry| println!("{entry}")),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let mut vm = Vm::with_inputs(vec![3, 11, 109, 4, 1001, 11, 5, 11, 204, 7, 99, 0], &[7]);
+        let mut entries = vec![];
+        let mut number = 0;
+        while let Some(step) = vm.step().unwrap() {
+            entries.push(TraceEntry { number, step });
+            number += 1;
+        }
+        assert_eq!(entries[0].encode(), "0 0 3 11 w=11:0:7 in=7");
+        assert_eq!(entries[1].encode(), "1 2 109 4 r=3 rb=0:4");
+        assert_eq!(entries[2].encode(), "2 4 1001 7,5,11 r=11:6 w=11:7:12");
+        assert_eq!(entries[3].encode(), "3 8 204 12 r=11 out=12");
+        assert_eq!(entries[4].encode(), "4 10 99 -");
+        for entry in entries {
+            assert_eq!(TraceEntry::parse(&entry.encode()).unwrap(), entry);
+        }
+        assert!(TraceEntry::parse("1 2 nope").is_err());
+    }
+
+    #[test]
+    fn test_trace_file_filter() {
+        let path = env::temp_dir().join("intcode_trace_test.txt");
+        let path = path.to_str().unwrap();
+        let mut tracer = Tracer::create(path).unwrap();
+        // counts memory[20] down from 3
+        let mut vm = Vm::new(vec![1101, 3, 0, 20, 1001, 20, -1, 20, 1005, 20, 4, 99]);
+        assert_eq!(vm.run_observed(Limits::default(), &mut tracer).unwrap(), Status::Halted);
+        assert_eq!(tracer.finish().unwrap(), 8);
+
+        let all = filter_trace(path, None, None).unwrap();
+        assert_eq!(all.len(), 8);
+        let jumps = filter_trace(path, None, Some(8..9)).unwrap();
+        assert_eq!(jumps.iter().map(|entry| entry.number).collect::<Vec<_>>(), vec![2, 4, 6]);
+        let writes = filter_trace(path, Some(1..5), Some(20..21)).unwrap();
+        assert_eq!(writes.iter().map(|entry| entry.number).collect::<Vec<_>>(), vec![1, 3]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use crate::intcode::{Instruction, Opcode, ParamMode};
+
+// writes past this address are treated as a program bug rather than grown into
+const MAX_MEMORY: usize = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    NeedsInput,
+    Halted,
+}
+
+// What `Sum` and `Multiply` do when the result doesn't fit in a cell. Plain `+` and `*`
+// would panic in debug builds and silently wrap in release ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    #[default]
+    Error,
+    Wrap,
+    Saturate,
+}
+
+impl Overflow {
+    pub const ALL: [Overflow; 3] = [Overflow::Error, Overflow::Wrap, Overflow::Saturate];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|overflow| format!("{overflow:?}").eq_ignore_ascii_case(name))
+    }
+}
+
+// The integer type a machine's memory is made of: i64 for every puzzle so far, i128 for
+// programs whose intermediate values outgrow it.
+pub trait Cell: Copy + Debug + Display + Default + Eq + Ord + Hash + From<i64> {
+    fn add_with(self, other: Self, overflow: Overflow) -> Option<Self>;
+    fn mul_with(self, other: Self, overflow: Overflow) -> Option<Self>;
+    fn to_address(self) -> Option<usize>;
+    fn from_address(address: usize) -> Self;
+    fn to_i64(self) -> Option<i64>;
+}
+
+macro_rules! impl_cell {
+    ($($cell:ty),*) => {$(
+        impl Cell for $cell {
+            fn add_with(self, other: Self, overflow: Overflow) -> Option<Self> {
+                match overflow {
+                    Overflow::Error => self.checked_add(other),
+                    Overflow::Wrap => Some(self.wrapping_add(other)),
+                    Overflow::Saturate => Some(self.saturating_add(other)),
+                }
+            }
+
+            fn mul_with(self, other: Self, overflow: Overflow) -> Option<Self> {
+                match overflow {
+                    Overflow::Error => self.checked_mul(other),
+                    Overflow::Wrap => Some(self.wrapping_mul(other)),
+                    Overflow::Saturate => Some(self.saturating_mul(other)),
+                }
+            }
+
+            fn to_address(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+
+            fn from_address(address: usize) -> Self {
+                address as $cell
+            }
+
+            fn to_i64(self) -> Option<i64> {
+                i64::try_from(self).ok()
+            }
+        }
+    )*};
+}
+
+impl_cell!(i64, i128);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Write<C: Cell = i64> {
+    pub address: usize,
+    pub old: C,
+    pub new: C,
+}
+
+// what a single executed instruction did; `operands` holds the values read for each
+// parameter, or the resolved address for the parameter that is written to, and `reads`
+// the cells the read parameters were loaded from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step<C: Cell = i64> {
+    pub instruction_pointer: usize,
+    pub instruction: Instruction,
+    pub operands: [C; 3],
+    pub reads: [Option<usize>; 2],
+    pub write: Option<Write<C>>,
+    pub relative_base: Option<(C, C)>,
+    pub input: Option<C>,
+    pub output: Option<C>,
+}
+
+// Guards for running programs that might never finish. Loop detection hashes the whole VM
+// state each time a backward jump is taken while no input is queued; seeing the same state
+// twice with no input read in between means the program can only ever repeat itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub detect_loops: bool,
+}
+
+// everything a program printed, and whether it stopped on `Stop` or is stuck waiting on
+// input it was never given
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunResult {
+    pub outputs: Vec<i64>,
+    pub status: Status,
+}
+
+// Something that watches a run one instruction at a time, like a tracer or a profiler.
+// See `Machine::run_observed`.
+pub trait Observer<C: Cell = i64> {
+    fn step(&mut self, step: &Step<C>) -> Result<(), String>;
+
+    // called when the run reaches an input that hasn't been queued; a value returned here
+    // is queued and the run carries on
+    fn needs_input(&mut self) -> Result<Option<C>, String> {
+        Ok(None)
+    }
+}
+
+impl<C: Cell> Observer<C> for () {
+    fn step(&mut self, _: &Step<C>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Machine<C: Cell> {
+    pub memory: Vec<C>,
+    pub instruction_pointer: usize,
+    pub relative_base: C,
+    pub inputs: VecDeque<C>,
+    pub outputs: Vec<C>,
+    pub halted: bool,
+    pub overflow: Overflow,
+}
+
+pub type Vm = Machine<i64>;
+pub type WideVm = Machine<i128>;
+
+impl<C: Cell> Machine<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        Self {
+            memory: program,
+            instruction_pointer: 0,
+            relative_base: C::default(),
+            inputs: VecDeque::new(),
+            outputs: vec![],
+            halted: false,
+            overflow: Overflow::default(),
+        }
+    }
+
+    pub fn with_inputs(program: Vec<C>, inputs: &[C]) -> Self {
+        let mut vm = Self::new(program);
+        vm.inputs.extend(inputs);
+        vm
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn push_input(&mut self, value: C) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn take_outputs(&mut self) -> Vec<C> {
+        mem::take(&mut self.outputs)
+    }
+
+    // memory is unbounded to the right and reads as zero past the loaded program
+    pub fn read(&self, address: usize) -> C {
+        self.memory.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn write(&mut self, address: usize, value: C) -> Result<Write<C>, String> {
+        if address >= self.memory.len() {
+            if address >= MAX_MEMORY {
+                return Err(format!("address {} out of range at ip {}", address, self.instruction_pointer));
+            }
+            self.memory.resize(address + 1, C::default());
+        }
+        let old = mem::replace(&mut self.memory[address], value);
+        Ok(Write { address, old, new: value })
+    }
+
+    pub fn current_instruction(&self) -> Result<Instruction, String> {
+        let value = self.read(self.instruction_pointer);
+        value.to_i64().ok_or_else(|| format!("invalid instruction {value}"))
+            .and_then(Instruction::decode)
+            .map_err(|e| format!("{} at ip {}", e, self.instruction_pointer))
+    }
+
+    pub fn param_address(&self, instruction: Instruction, index: usize) -> Result<usize, String> {
+        let cell = self.instruction_pointer + 1 + index;
+        let address = match instruction.modes[index] {
+            ParamMode::Positional => self.read(cell),
+            ParamMode::Immediate => return Ok(cell),
+            ParamMode::Relative => self.relative_base.add_with(self.read(cell), Overflow::Error)
+                .ok_or_else(|| format!("relative address overflowed at ip {}", self.instruction_pointer))?,
+        };
+        address.to_address().ok_or_else(|| format!("negative address {} at ip {}", address, self.instruction_pointer))
+    }
+
+    fn param(&self, instruction: Instruction, index: usize, step: &mut Step<C>) -> Result<C, String> {
+        let address = self.param_address(instruction, index)?;
+        step.reads[index] = Some(address);
+        Ok(self.read(address))
+    }
+
+    pub fn status(&self) -> Status {
+        if self.halted {
+            return Status::Halted;
+        }
+        match self.current_instruction() {
+            Ok(instruction) if instruction.opcode == Opcode::Input && self.inputs.is_empty() => Status::NeedsInput,
+            _ => Status::Running,
+        }
+    }
+
+    // Executes one instruction. Returns None without touching any state when the VM has
+    // halted or is waiting on an empty input queue.
+    pub fn step(&mut self) -> Result<Option<Step<C>>, String> {
+        if self.halted {
+            return Ok(None);
+        }
+        let instruction = self.current_instruction()?;
+        let mut step = Step {
+            instruction_pointer: self.instruction_pointer,
+            instruction,
+            operands: [C::default(); 3],
+            reads: [None; 2],
+            write: None,
+            relative_base: None,
+            input: None,
+            output: None,
+        };
+        let mut next_ip = self.instruction_pointer + instruction.size();
+
+        match instruction.opcode {
+            Opcode::Sum | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+                let param1 = self.param(instruction, 0, &mut step)?;
+                let param2 = self.param(instruction, 1, &mut step)?;
+                let value = match instruction.opcode {
+                    Opcode::Sum => param1.add_with(param2, self.overflow),
+                    Opcode::Multiply => param1.mul_with(param2, self.overflow),
+                    Opcode::LessThan => Some(C::from((param1 < param2) as i64)),
+                    _ => Some(C::from((param1 == param2) as i64)),
+                };
+                let value = value.ok_or_else(|| format!("{:?} of {} and {} overflowed at ip {}", instruction.opcode, param1, param2, self.instruction_pointer))?;
+                let address = self.param_address(instruction, 2)?;
+                step.operands = [param1, param2, C::from_address(address)];
+                step.write = Some(self.write(address, value)?);
+            }
+            Opcode::Input => {
+                let address = self.param_address(instruction, 0)?;
+                let Some(value) = self.inputs.pop_front() else { return Ok(None) };
+                step.operands[0] = C::from_address(address);
+                step.input = Some(value);
+                step.write = Some(self.write(address, value)?);
+            }
+            Opcode::Output => {
+                let value = self.param(instruction, 0, &mut step)?;
+                self.outputs.push(value);
+                step.operands[0] = value;
+                step.output = Some(value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let param1 = self.param(instruction, 0, &mut step)?;
+                let param2 = self.param(instruction, 1, &mut step)?;
+                step.operands = [param1, param2, C::default()];
+                if (param1 != C::default()) == (instruction.opcode == Opcode::JumpIfTrue) {
+                    next_ip = param2.to_address()
+                        .ok_or_else(|| format!("jump to negative address {} at ip {}", param2, self.instruction_pointer))?;
+                }
+            }
+            Opcode::RelativeBaseOffset => {
+                let offset = self.param(instruction, 0, &mut step)?;
+                let relative_base = self.relative_base.add_with(offset, self.overflow)
+                    .ok_or_else(|| format!("relative base overflowed at ip {}", self.instruction_pointer))?;
+                step.operands[0] = offset;
+                step.relative_base = Some((self.relative_base, relative_base));
+                self.relative_base = relative_base;
+            }
+            Opcode::Stop => {
+                self.halted = true;
+                next_ip = self.instruction_pointer;
+            }
+        }
+        self.instruction_pointer = next_ip;
+        Ok(Some(step))
+    }
+
+    // runs until the program halts or needs an input that hasn't been queued
+    pub fn run(&mut self) -> Result<Status, String> {
+        while self.step()?.is_some() {}
+        Ok(self.status())
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.instruction_pointer.hash(&mut hasher);
+        self.relative_base.hash(&mut hasher);
+        self.memory.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // `run` that gives up with an error once the step budget is spent or a loop is found
+    pub fn run_with_limits(&mut self, limits: Limits) -> Result<Status, String> {
+        self.run_observed(limits, &mut ())
+    }
+
+    // `run_with_limits` that shows every executed instruction to `observer`, and asks it for
+    // more input before stopping on an empty queue
+    pub fn run_observed(&mut self, limits: Limits, observer: &mut impl Observer<C>) -> Result<Status, String> {
+        let mut steps = 0;
+        let mut seen = HashSet::new();
+        loop {
+            if limits.max_steps == Some(steps) && self.status() == Status::Running {
+                return Err(format!("step limit of {} reached at ip {}", steps, self.instruction_pointer));
+            }
+            let Some(step) = self.step()? else {
+                if self.status() == Status::NeedsInput {
+                    if let Some(value) = observer.needs_input()? {
+                        // the state hash doesn't cover inputs, so states seen before this
+                        // one was read prove nothing about the states after it
+                        seen.clear();
+                        self.push_input(value);
+                        continue;
+                    }
+                }
+                return Ok(self.status());
+            };
+            steps += 1;
+            observer.step(&step)?;
+
+            let jumped_back = matches!(step.instruction.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+                && self.instruction_pointer <= step.instruction_pointer;
+            if limits.detect_loops && jumped_back && self.inputs.is_empty() && !seen.insert(self.state_hash()) {
+                return Err(format!("stuck in a loop at ip {}", self.instruction_pointer));
+            }
+        }
+    }
+}
+
+pub fn run_program(program: Vec<i64>, inputs: &[i64]) -> Result<RunResult, String> {
+    let mut vm = Vm::with_inputs(program, inputs);
+    let status = vm.run()?;
+    Ok(RunResult { outputs: vm.outputs, status })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_vec() -> Vec<i64> {
+        vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = create_vec();
+        let mut vm = Vm::new(program.clone());
+        assert_eq!(vm.run().unwrap(), Status::Halted);
+        assert_eq!(vm.outputs, program);
+    }
+
+    #[test]
+    fn test_input_blocking() {
+        // outputs 1 if the input equals 8, 0 otherwise
+        let mut vm = Vm::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        assert_eq!(vm.run().unwrap(), Status::NeedsInput);
+        assert_eq!(vm.step().unwrap(), None);
+        vm.push_input(8);
+        assert_eq!(vm.run().unwrap(), Status::Halted);
+        assert_eq!(vm.take_outputs(), vec![1]);
+        assert_eq!(vm.step().unwrap(), None);
+    }
+
+    #[test]
+    fn test_step_write() {
+        let mut vm = Vm::new(vec![1002, 4, 3, 4, 33]);
+        let step = vm.step().unwrap().unwrap();
+        assert_eq!(step.write, Some(Write { address: 4, old: 33, new: 99 }));
+        assert_eq!(vm.run().unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn test_observer() {
+        // counts executed instructions and answers each input request with the next value
+        struct Feeder {
+            steps: usize,
+            values: Vec<i64>,
+        }
+        impl Observer for Feeder {
+            fn step(&mut self, _: &Step) -> Result<(), String> {
+                self.steps += 1;
+                Ok(())
+            }
+            fn needs_input(&mut self) -> Result<Option<i64>, String> {
+                Ok(self.values.pop())
+            }
+        }
+        let mut vm = Vm::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+        let mut feeder = Feeder { steps: 0, values: vec![6, 5] };
+        assert_eq!(vm.run_observed(Limits::default(), &mut feeder).unwrap(), Status::Halted);
+        assert_eq!(vm.outputs, vec![5, 6]);
+        assert_eq!(feeder.steps, 5);
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let mut vm = Vm::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![1219070632396864]);
+        let mut vm = Vm::new(vec![104, 1125899906842624, 99]);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![1125899906842624]);
+    }
+
+    #[test]
+    fn test_run_program() {
+        let result = run_program(vec![3, 0, 4, 0, 3, 0, 4, 0, 99], &[5]).unwrap();
+        assert_eq!(result, RunResult { outputs: vec![5], status: Status::NeedsInput });
+        let result = run_program(vec![3, 0, 4, 0, 3, 0, 4, 0, 99], &[5, 6]).unwrap();
+        assert_eq!(result, RunResult { outputs: vec![5, 6], status: Status::Halted });
+    }
+
+    #[test]
+    fn test_step_limit() {
+        // counts up in memory[9] forever, so the state never repeats
+        let mut vm = Vm::new(vec![1001, 9, 1, 9, 1105, 1, 0, 99, 0, 0]);
+        let limits = Limits { max_steps: Some(1000), detect_loops: true };
+        assert_eq!(vm.run_with_limits(limits).unwrap_err(), "step limit of 1000 reached at ip 0");
+        assert_eq!(vm.read(9), 500);
+
+        let mut vm = Vm::new(create_vec());
+        assert_eq!(vm.run_with_limits(Limits { max_steps: Some(1000), detect_loops: false }).unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        // day2 style program whose patched jump target spins on itself
+        let mut vm = Vm::new(vec![1, 0, 0, 0, 1106, 0, 4, 99]);
+        let limits = Limits { max_steps: None, detect_loops: true };
+        assert_eq!(vm.run_with_limits(limits).unwrap_err(), "stuck in a loop at ip 4");
+
+        // an input-driven loop is only stuck once it stops reading input
+        let mut vm = Vm::with_inputs(vec![3, 20, 1005, 20, 0, 1105, 1, 5], &[1, 2, 0]);
+        assert_eq!(vm.run_with_limits(limits).unwrap_err(), "stuck in a loop at ip 5");
+        let mut vm = Vm::with_inputs(vec![3, 20, 1006, 20, 0, 99], &[0, 0, 3]);
+        assert_eq!(vm.run_with_limits(limits).unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        // squares a large input, then adds one to the result
+        let program = vec![3, 13, 2, 13, 13, 13, 1001, 13, 1, 13, 4, 13, 99, 0];
+        let mut vm = Vm::with_inputs(program.clone(), &[i64::MAX / 2]);
+        assert_eq!(vm.run().unwrap_err(), format!("Multiply of {0} and {0} overflowed at ip 2", i64::MAX / 2));
+
+        let mut vm = Vm::with_inputs(program.clone(), &[i64::MAX / 2]).with_overflow(Overflow::Wrap);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![(i64::MAX / 2).wrapping_mul(i64::MAX / 2) + 1]);
+
+        let mut vm = Vm::with_inputs(program.clone(), &[i64::MAX / 2]).with_overflow(Overflow::Saturate);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![i64::MAX]);
+
+        let mut vm = WideVm::with_inputs(program.into_iter().map(i128::from).collect(), &[i64::MAX as i128 / 2]);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![(i64::MAX as i128 / 2) * (i64::MAX as i128 / 2) + 1]);
+    }
+
+    #[test]
+    fn test_wide_cells() {
+        let mut vm = WideVm::new(create_vec().into_iter().map(i128::from).collect());
+        assert_eq!(vm.run().unwrap(), Status::Halted);
+        assert_eq!(vm.outputs, create_vec().into_iter().map(i128::from).collect::<Vec<_>>());
+
+        let mut vm = WideVm::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        vm.run().unwrap();
+        assert_eq!(vm.outputs, vec![1219070632396864]);
+        // instructions still have to fit the usual encoding
+        assert!(WideVm::new(vec![i128::MAX]).run().unwrap_err().contains("invalid instruction"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Vm::new(vec![42]).run().unwrap_err().contains("Opcode not matched"));
+        assert!(Vm::new(vec![4, -3, 99]).run().unwrap_err().contains("negative address"));
+    }
+}
@@ -1,15 +1,14 @@
 #![allow(unused_mut, unused_variables, dead_code, unused_imports)]
 
-use std::fs;
+use std::{env, fs};
 use crate::day10::{destroy_asteroids, find_best_location};
 use crate::day12::{calculate_repeating_index, calculate_repetition_period, calculate_total_energy, full_steps_energy_period};
 use crate::day14::{Inventory, RecipeBook};
 use crate::day7::{threaded_feedback_loop_phase_combinations, try_phase_combinations};
 use crate::day8::{assemble_image, find_smallest0_mult1_2};
-// use crate::day9::run_computer;
 // use crate::day6::{count_orbits, find_number_of_jumps, insert_orbits_to_hashmap, parse_pairs};
 //use day1::{fuel_req, fuel_req_recursive};
-//use day2::{computer, pair_computer};
+//use day2::{pair_computer, patched_computer};
 //use day3::{wire_crossing_manhattan, wire_crossing_steps};
 //use day4::{extra_num_possible_passwords_for_container, num_possible_passwords_for_container};
 // use day5::computer_ver2;
@@ -28,8 +27,26 @@ mod day11;
 mod day12;
 mod day13;
 mod day14;
+mod intcode;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("debug") => return intcode::debugger::main(&args[1..]),
+        Some("trace") => return intcode::trace::main(&args[1..]),
+        Some("trace-filter") => return intcode::trace::filter_main(&args[1..]),
+        Some("profile") => return intcode::profile::main(&args[1..]),
+        Some("cfg") => return intcode::cfg::main(&args[1..]),
+        Some("analyse") => return intcode::analysis::main(&args[1..]),
+        Some("ascii") => return intcode::ascii::main(&args[1..]),
+        Some("selfmod") => return intcode::selfmod::main(&args[1..]),
+        Some("bench") => return intcode::compiled::main(&args[1..]),
+        Some("intcode") => return intcode::cli::main(&args[1..]),
+        Some("dump") => return intcode::dump::main(&args[1..]),
+        Some("memdiff") => return intcode::dump::diff_main(&args[1..]),
+        _ => {}
+    }
+
     /*
     let inputs = fs::read("./inputs/day1").unwrap();
     let inputs_vec: Vec<i32> = String::from_utf8(inputs)
@@ -64,11 +81,10 @@ fn main() {
             }
         })
         .collect();
-    inputs_vec[1] = 12;
-    inputs_vec[2] = 2;
     println!("{:?}", inputs_vec);
 
-    let res3 = computer(inputs_vec.clone());
+    // or `intcode ./inputs/day2 --patch ./inputs/patches:1202-alarm --print 0`
+    let res3 = patched_computer(inputs_vec.clone(), &intcode::patch::alarm_1202()).unwrap();
     println!("result returned from the computer: {:?}", res3[0]);
     let expected_result = 19690720;
     let (noun, verb) = pair_computer(inputs_vec, expected_result).unwrap();
@@ -178,7 +194,7 @@ fn main() {
         .collect();
 
     let set = vec![0, 1, 2, 3, 4];
-    println!("{}", try_phase_combinations(inputs_vec.clone(), set.clone()));
+    println!("{:?}", try_phase_combinations(inputs_vec.clone(), set.clone()));
     let set = vec![5, 6, 7, 8, 9];
     println!("{}", threaded_feedback_loop_phase_combinations(inputs_vec.clone(), set));
 
@@ -205,8 +221,9 @@ fn main() {
         })
         .collect();
 
-    println!("result: {:?}", run_computer(inputs_vec.clone(), 1));
-    println!("result: {:?}", run_computer(inputs_vec.clone(), 2));
+    // or `intcode ./inputs/day9 1`
+    println!("result: {:?}", intcode::vm::run_program(inputs_vec.clone(), &[1]));
+    println!("result: {:?}", intcode::vm::run_program(inputs_vec.clone(), &[2]));
 
 
     println!("Day - 10");